@@ -1,16 +1,429 @@
+use eyre::{eyre, Report, Result};
+use indexmap::IndexMap;
 use linya::{Bar, Progress};
 use rayon::prelude::*;
-use std::sync::Mutex;
-use unicode_segmentation::UnicodeSegmentation;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, sync::Mutex};
+
+use crate::distribution::LengthDistributions;
+use crate::filters::{dedup::Deduplication, language::LanguageIdentification, sample::Sampling};
+use crate::occurance::{
+    Countable, OccuranceAnalysis, OccuranceCounter, OccuranceT, Occurances, PositionalOccurances,
+};
+use crate::segmentation::{Normalization, Segmentation};
+use crate::sketch::{Approximations, SpaceSaving};
+use crate::sources::wortschatz::SourceSelection;
+use crate::words::{WordTokenization, WordTokenizer};
+
+/// Marks the start of a sentence when sentence boundaries are enabled
+pub const SENTENCE_START: &str = "␂";
+/// Marks the end of a sentence when sentence boundaries are enabled
+pub const SENTENCE_END: &str = "␃";
+/// Marks the start of a word when word boundaries are enabled
+pub const WORD_START: &str = "⟨";
+/// Marks the end of a word when word boundaries are enabled
+pub const WORD_END: &str = "⟩";
+/// Joins the words of word ngrams
+pub const WORD_SEPARATOR: &str = " ";
+/// Typed between sentences when ngrams span sentence boundaries
+const SENTENCE_SEPARATOR: &str = " ";
+/// Marks a skipped position in a skipgram shape
+const SKIP: char = '_';
+/// Sentences counted together before their tables are pruned and merged
+const PRUNING_CHUNK: usize = 1024;
+
+/// Parameters of an analysis.
+/// These are stored with the analysis, such that it is known what was counted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnalysisSpecification {
+    pub ngram_ns: Vec<usize>,
+    pub skipgram_ns: Vec<usize>,
+
+    #[serde(default)]
+    pub boundaries: Boundaries,
+
+    /// Units that sentences are split into. Analyses made before it was recorded used graphemes
+    #[serde(default)]
+    pub segmentation: Segmentation,
+
+    /// Unicode normalisation applied to sentences before they are split into units
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<Normalization>,
+
+    /// How sentences are split into the words of word tables and length distributions
+    #[serde(default)]
+    pub words: WordTokenization,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipgram_shapes: Vec<SkipgramShape>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pruning: Option<Pruning>,
+
+    /// Numbers of consecutive words counted as word ngrams
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub word_ngram_ns: Vec<usize>,
+
+    /// Pruning of word ngrams, which are kept in full if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_ngram_pruning: Option<Pruning>,
+
+    /// Ngram orders counted approximately, along with the number of entries kept for each
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub approximate: IndexMap<usize, usize>,
+
+    /// Number of shards the sentences are split into and also counted separately,
+    /// such that confidence intervals can be bootstrapped from them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_shards: Option<usize>,
+
+    /// Count only a sample of the sentences
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Sampling>,
+
+    /// Drop repeated sentences before counting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deduplication: Option<Deduplication>,
+
+    /// Keep only sentences identified as one language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<LanguageIdentification>,
+
+    /// Keep only sentences of some sources, for corpora that link sentences to their sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<SourceSelection>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Boundaries {
+    /// Mark the start and end of each sentence
+    pub sentence: bool,
+    /// Mark the start and end of each word
+    pub word: bool,
+    /// Count ngrams continuing from one sentence into the next, as a typist would type them
+    pub span_sentences: bool,
+}
+
+/// Drops rare ngrams and skipgrams while counting, keeping the tables of large orders in memory.
+/// Tables growing beyond `max_entries` are cut down to the most frequent half of that,
+/// and to entries occurring at least `min_count` times once counting is done.
+/// As entries dropped mid-way may occur again later, counts of rare entries are approximate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pruning {
+    /// Entries occurring fewer times are dropped
+    pub min_count: usize,
+    /// Tables are pruned whenever they grow beyond this many entries
+    pub max_entries: usize,
+}
+
+impl Occurances<usize> {
+    /// Cuts a table larger than `max_entries` down to at most half as many entries, by dropping
+    /// those occurring fewer than `min_count` times or as far above that as needed
+    fn prune(&mut self, min_count: usize, max_entries: usize) {
+        if self.len() <= max_entries {
+            return;
+        }
+
+        let low_water = max_entries / 2;
+        let mut counts: Vec<usize> = self.iter().map(|(_, &count)| count).collect();
+        let (_, &mut highest_dropped, _) =
+            counts.select_nth_unstable_by(low_water, |a, b| b.cmp(a));
+        let threshold = min_count.max(highest_dropped + 1);
+
+        self.retain(|_, count| *count >= threshold);
+    }
+}
+
+impl OccuranceAnalysis<usize> {
+    /// Prunes ngram and skipgram tables larger than `max_entries`. With `max_entries` of `None`,
+    /// only entries occurring fewer than `min_count` times are dropped
+    fn prune(&mut self, min_count: usize, max_entries: Option<usize>) {
+        self.ngrams
+            .values_mut()
+            .chain(self.skipgrams.values_mut())
+            .chain(self.shaped_skipgrams.values_mut())
+            .for_each(|occurances| match max_entries {
+                Some(max_entries) => occurances.prune(min_count, max_entries),
+                None => occurances.retain(|_, count| *count >= min_count),
+            });
+    }
+
+    /// Prunes word ngram tables like `prune`
+    fn prune_word_ngrams(&mut self, min_count: usize, max_entries: Option<usize>) {
+        self.word_ngrams
+            .values_mut()
+            .for_each(|occurances| match max_entries {
+                Some(max_entries) => occurances.prune(min_count, max_entries),
+                None => occurances.retain(|_, count| *count >= min_count),
+            });
+    }
+}
+
+/// The shape of a skipgram, such as `a_b`, `a__b`, `ab_c` or `a_bc`.
+/// Letters are positions that are kept, underscores positions that are skipped.
+/// Each shape is counted in a table of its own, keyed by the kept graphemes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct SkipgramShape {
+    shape: String,
+    kept: Vec<usize>,
+}
+
+impl SkipgramShape {
+    /// Number of graphemes spanned by the shape
+    pub fn len(&self) -> usize {
+        self.shape.chars().count()
+    }
+
+    /// Joins the kept graphemes of a window of `len()` graphemes
+    pub fn key(&self, window: &[&str]) -> Countable {
+        self.kept.iter().map(|&i| window[i]).collect()
+    }
+}
+
+impl FromStr for SkipgramShape {
+    type Err = Report;
+
+    fn from_str(shape: &str) -> Result<Self> {
+        let chars: Vec<char> = shape.chars().collect();
+
+        let is_valid = chars.len() >= 3
+            && chars.first() != Some(&SKIP)
+            && chars.last() != Some(&SKIP)
+            && chars.contains(&SKIP);
+        if !is_valid {
+            return Err(eyre!(
+                "Invalid skipgram shape '{shape}'. Shapes start and end with a letter and skip at least one position with '{SKIP}', such as 'a_b' or 'ab_c'"
+            ));
+        }
+
+        Ok(Self {
+            shape: shape.to_owned(),
+            kept: (0..chars.len()).filter(|&i| chars[i] != SKIP).collect(),
+        })
+    }
+}
+
+impl TryFrom<String> for SkipgramShape {
+    type Error = Report;
+
+    fn try_from(shape: String) -> Result<Self> {
+        shape.parse()
+    }
+}
 
-use crate::occurance::{Countable, OccuranceAnalysis, OccuranceCounter, Occurances};
+impl From<SkipgramShape> for String {
+    fn from(shape: SkipgramShape) -> Self {
+        shape.shape
+    }
+}
+
+impl fmt::Display for SkipgramShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.shape)
+    }
+}
 
+impl AnalysisSpecification {
+    /// Size of the largest window needed to count the ngrams and skipgrams
+    fn max_window(&self) -> usize {
+        let ngram_max = self.ngram_ns.iter().copied().max().unwrap_or(0);
+        let skipgram_max = self.skipgram_ns.iter().map(|n| n + 2).max().unwrap_or(0);
+        let shape_max = self
+            .skipgram_shapes
+            .iter()
+            .map(|s| s.len())
+            .max()
+            .unwrap_or(0);
+
+        ngram_max.max(skipgram_max).max(shape_max)
+    }
+}
+
+/// Analyses sentences, splitting them into words with `words`,
+/// which must match the word tokenisation of the specification
 pub fn analyse(
-    sentences: &Vec<String>,
-    ngram_ns: Vec<usize>,
-    skipgram_ns: Vec<usize>,
+    sentences: &[String],
+    specification: &AnalysisSpecification,
+    words: &WordTokenizer,
     show_progress: bool,
-) -> OccuranceAnalysis<usize> {
+) -> (OccuranceAnalysis<usize>, Approximations) {
+    let mark_words = specification.boundaries.word;
+    let segmentation = specification.segmentation;
+    let word_ngram_ns = &specification.word_ngram_ns;
+
+    analyse_units(
+        sentences,
+        specification,
+        show_progress,
+        |sentence| {
+            let units = segmentation.units(sentence);
+
+            if mark_words {
+                mark_word_boundaries(units)
+            } else {
+                units
+            }
+        },
+        |occ_analysis, sentence| {
+            let sentence_words = words.words(sentence);
+
+            let counter: OccuranceCounter = sentence_words
+                .iter()
+                .map(|&word| Countable::from(word))
+                .collect();
+            occ_analysis.words += counter.into();
+
+            for &n in word_ngram_ns {
+                let word_ngrams = occ_analysis.word_ngrams.entry(n).or_default();
+                for window in sentence_words.windows(n) {
+                    word_ngrams.increment(window.join(WORD_SEPARATOR).into(), 1);
+                }
+            }
+
+            sentence_words
+                .iter()
+                .for_each(|word| count_positions(&mut occ_analysis.positions, word, segmentation));
+
+            count_lengths(
+                &mut occ_analysis.lengths,
+                sentence,
+                &sentence_words,
+                segmentation,
+            );
+        },
+    )
+}
+
+/// Analyses sequences of keys, such as those from a keystroke log.
+/// Each key is a single unit of the resulting ngrams and skipgrams.
+pub fn analyse_keys(
+    sequences: &[Vec<Countable>],
+    specification: &AnalysisSpecification,
+    show_progress: bool,
+) -> (OccuranceAnalysis<usize>, Approximations) {
+    analyse_units(
+        sequences,
+        specification,
+        show_progress,
+        |keys| keys.iter().map(|key| key.as_str()).collect(),
+        |_, _| {},
+    )
+}
+
+/// Counts the units of a word by their position within it
+fn count_positions(
+    positions: &mut PositionalOccurances<usize>,
+    word: &str,
+    segmentation: Segmentation,
+) {
+    let units: Vec<&str> = segmentation.units(word);
+    let last = units.len().saturating_sub(1);
+
+    for (i, &unit) in units.iter().enumerate() {
+        if i == 0 {
+            positions.initial.increment(unit.into(), 1);
+        }
+        if i == last {
+            positions.final_.increment(unit.into(), 1);
+        }
+        if i != 0 && i != last {
+            positions.medial.increment(unit.into(), 1);
+        }
+
+        positions
+            .indexed
+            .entry(i + 1)
+            .or_default()
+            .increment(unit.into(), 1);
+    }
+}
+
+/// Counts the lengths of a sentence and its words, in units of the segmentation
+fn count_lengths(
+    lengths: &mut LengthDistributions<usize>,
+    sentence: &str,
+    words: &[&str],
+    segmentation: Segmentation,
+) {
+    for word in words {
+        lengths
+            .word_units
+            .increment(segmentation.units(word).len(), 1);
+    }
+
+    lengths.sentence_words.increment(words.len(), 1);
+    lengths
+        .sentence_units
+        .increment(segmentation.units(sentence).len(), 1);
+}
+
+/// Surrounds each run of non-whitespace graphemes with word boundary markers
+fn mark_word_boundaries(graphemes: Vec<&str>) -> Vec<&str> {
+    let mut marked = Vec::with_capacity(graphemes.len() * 2);
+    let mut in_word = false;
+
+    for grapheme in graphemes {
+        let is_whitespace = grapheme.chars().all(char::is_whitespace);
+
+        if is_whitespace && in_word {
+            marked.push(WORD_END);
+        } else if !is_whitespace && !in_word {
+            marked.push(WORD_START);
+        }
+
+        in_word = !is_whitespace;
+        marked.push(grapheme);
+    }
+
+    if in_word {
+        marked.push(WORD_END);
+    }
+
+    marked
+}
+
+/// Counts of a run of sentences, along with the index and tail of the last sentence counted
+type Counts<'s> = (
+    OccuranceAnalysis<usize>,
+    IndexMap<usize, SpaceSaving>,
+    Option<(usize, Vec<&'s str>)>,
+);
+
+/// Counts ngrams and skipgrams over the units that `units` splits each item into.
+/// `record` may record additional occurances of each item, such as words.
+/// Also returns the error bounds of the ngram orders that were counted approximately.
+fn analyse_units<'s, S, U, R>(
+    sentences: &'s [S],
+    specification: &AnalysisSpecification,
+    show_progress: bool,
+    units: U,
+    record: R,
+) -> (OccuranceAnalysis<usize>, Approximations)
+where
+    S: Sync,
+    U: for<'a> Fn(&'a S) -> Vec<&'a str> + Sync,
+    R: Fn(&mut OccuranceAnalysis<usize>, &S) + Sync,
+{
+    let ngram_ns = &specification.ngram_ns;
+    let skipgram_ns = &specification.skipgram_ns;
+    let skipgram_shapes = &specification.skipgram_shapes;
+    let pruning = &specification.pruning;
+    let word_ngram_pruning = &specification.word_ngram_pruning;
+    let approximate = &specification.approximate;
+    let boundaries = &specification.boundaries;
+    let max_window = specification.max_window();
+
+    let sentence_units = |sentence| {
+        let units = units(sentence);
+
+        if boundaries.sentence {
+            [&[SENTENCE_START], &units[..], &[SENTENCE_END]].concat()
+        } else {
+            units
+        }
+    };
+
     let progress: Option<Mutex<Progress>>;
     let bar: Option<Bar>;
 
@@ -29,146 +442,477 @@ pub fn analyse(
         bar = None;
     }
 
-    let skipgram_ns_incremented: Vec<usize> = skipgram_ns.iter().map(|&x| x + 2).collect();
-    let ngram_ns: Vec<usize> = ngram_ns.iter().map(|&x| x as usize).collect();
-
-    let mut window_size: Vec<usize> = [skipgram_ns_incremented, ngram_ns.clone()].concat();
-    window_size.sort_unstable(); // Sort the vector to prepare for deduplication
-    window_size.dedup(); // Remove duplicates
-
-    let mut occ_analysis: OccuranceAnalysis<usize> = sentences
-        .par_iter()
-        .fold(
-            || OccuranceAnalysis::default(),
-            |mut occ_analysis: OccuranceAnalysis<usize>, sentence: &String| {
-                let graphemes = sentence.graphemes(true).collect::<Vec<_>>();
-
-                ngram_ns.iter().for_each(|n| {
-                    let windows = graphemes.windows(*n);
-
-                    let grams: OccuranceCounter = windows
-                        .into_iter()
-                        .filter(|&x| x.len() == *n)
-                        // Iterator over arrays of graphemes
-                        .map(|x| Countable::from(x.join("")))
-                        .collect::<OccuranceCounter>();
-
-                    let ngrams_map: Occurances<_> = grams.into();
-                    let ngrams_entry = occ_analysis.ngrams.entry(*n).or_default();
-                    *ngrams_entry += ngrams_map;
-                });
-
-                skipgram_ns.iter().for_each(|n| {
-                    let skip_n = n + 2;
-                    let windows = graphemes.windows(skip_n);
-
-                    let skipgrams: OccuranceCounter = windows
-                        .into_iter()
-                        .filter(|&x| x.len() == skip_n)
-                        // Iterator over arrays of graphemes
-                        .map(|x| {
-                            let first_last = [*x.first().unwrap(), *x.last().unwrap()];
-                            Countable::from(first_last.join(""))
-                        })
-                        .collect::<OccuranceCounter>();
-
-                    let skipgrams_map: Occurances<_> = skipgrams.into();
-                    let skipgrams_entry = occ_analysis.skipgrams.entry(*n).or_default();
-                    *skipgrams_entry += skipgrams_map;
-                });
-
-                let words: OccuranceCounter = sentence
-                    .split_whitespace()
-                    .map(|x| Countable::from(x))
-                    .collect();
-                occ_analysis.words += words.into();
-                occ_analysis.num_sentences += 1;
-
-                if show_progress {
-                    progress
-                        .as_ref()
-                        .unwrap()
-                        .lock()
-                        .unwrap()
-                        .inc_and_draw(bar.as_ref().unwrap(), 1);
-                }
+    let new_sketches = || -> IndexMap<usize, SpaceSaving> {
+        approximate
+            .iter()
+            .filter(|(n, _)| ngram_ns.contains(n))
+            .map(|(&n, &capacity)| (n, SpaceSaving::new(capacity)))
+            .collect()
+    };
+
+    let prune_bounded = |occ_analysis: &mut OccuranceAnalysis<usize>| {
+        if let Some(pruning) = pruning {
+            occ_analysis.prune(pruning.min_count, Some(pruning.max_entries));
+        }
+        if let Some(pruning) = word_ngram_pruning {
+            occ_analysis.prune_word_ngrams(pruning.min_count, Some(pruning.max_entries));
+        }
+    };
+
+    // Units ending the sentences before sentence `i`, joined by separators. Only needed for the
+    // first sentence of each run counted in order, later ones carry the tail forward.
+    let tail_before = |i: usize| -> Vec<&'s str> {
+        let tail_len = max_window.saturating_sub(1);
+        let mut previous_units: Vec<Vec<&str>> = Vec::new();
+        let mut len = 0;
+        for previous in sentences[..i].iter().rev() {
+            if len >= tail_len {
+                break;
+            }
+            let units = sentence_units(previous);
+            if !units.is_empty() {
+                len += units.len() + 1;
+                previous_units.push(units);
+            }
+        }
+        previous_units.reverse();
+
+        let tail = previous_units.join(&SENTENCE_SEPARATOR);
+        tail[tail.len().saturating_sub(tail_len)..].to_vec()
+    };
+
+    let new_counts = || -> Counts<'s> { (OccuranceAnalysis::default(), new_sketches(), None) };
+
+    let count = |(mut occ_analysis, mut sketches, previous): Counts<'s>,
+                 (i, sentence): (usize, &'s S)|
+     -> Counts<'s> {
+        let mut graphemes = sentence_units(sentence);
+        record(&mut occ_analysis, sentence);
+
+        // Prefix the end of the preceding sentences, such that windows spanning
+        // them are counted. Windows within the prefix were counted already.
+        let mut prefix_len = 0;
+        let mut next = None;
+        if boundaries.span_sentences {
+            let tail = match previous {
+                Some((previous_i, tail)) if previous_i + 1 == i => tail,
+                _ => tail_before(i),
+            };
+
+            let units_len = graphemes.len();
+            if i > 0 {
+                prefix_len = tail.len();
+                graphemes = [&tail[..], &[SENTENCE_SEPARATOR], &graphemes[..]].concat();
+            }
+
+            // The tail of the next sentence ends with the units of this one
+            let next_tail = if units_len == 0 {
+                tail
+            } else {
+                let ending = match tail.is_empty() {
+                    true => &graphemes[graphemes.len() - units_len..],
+                    false => &graphemes[..],
+                };
+                ending[ending.len().saturating_sub(max_window.saturating_sub(1))..].to_vec()
+            };
+            next = Some((i, next_tail));
+        }
+
+        ngram_ns.iter().for_each(|n| {
+            let windows = graphemes.windows(*n);
+
+            let grams: OccuranceCounter = windows
+                .into_iter()
+                .skip(prefix_len.saturating_sub(n - 1))
+                .filter(|&x| x.len() == *n)
+                // Iterator over arrays of graphemes
+                .map(|x| Countable::from(x.join("")))
+                .collect::<OccuranceCounter>();
+
+            if let Some(sketch) = sketches.get_mut(n) {
+                grams
+                    .into_iter()
+                    .for_each(|(gram, count)| sketch.insert(gram, count));
+                return;
+            }
+
+            let ngrams_map: Occurances<_> = grams.into();
+            let ngrams_entry = occ_analysis.ngrams.entry(*n).or_default();
+            *ngrams_entry += ngrams_map;
+        });
+
+        skipgram_ns.iter().for_each(|n| {
+            let skip_n = n + 2;
+            let windows = graphemes.windows(skip_n);
+
+            let skipgrams: OccuranceCounter = windows
+                .into_iter()
+                .skip(prefix_len.saturating_sub(skip_n - 1))
+                .filter(|&x| x.len() == skip_n)
+                // Iterator over arrays of graphemes
+                .map(|x| {
+                    let first_last = [*x.first().unwrap(), *x.last().unwrap()];
+                    Countable::from(first_last.join(""))
+                })
+                .collect::<OccuranceCounter>();
+
+            let skipgrams_map: Occurances<_> = skipgrams.into();
+            let skipgrams_entry = occ_analysis.skipgrams.entry(*n).or_default();
+            *skipgrams_entry += skipgrams_map;
+        });
+
+        skipgram_shapes.iter().for_each(|shape| {
+            let len = shape.len();
+            let windows = graphemes.windows(len);
+
+            let skipgrams: OccuranceCounter = windows
+                .into_iter()
+                .skip(prefix_len.saturating_sub(len - 1))
+                .map(|x| shape.key(x))
+                .collect::<OccuranceCounter>();
+
+            let skipgrams_map: Occurances<_> = skipgrams.into();
+            let skipgrams_entry = occ_analysis
+                .shaped_skipgrams
+                .entry(shape.to_string())
+                .or_default();
+            *skipgrams_entry += skipgrams_map;
+        });
+
+        occ_analysis.num_sentences += 1;
+
+        if show_progress {
+            progress
+                .as_ref()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .inc_and_draw(bar.as_ref().unwrap(), 1);
+        }
+
+        (occ_analysis, sketches, next)
+    };
+
+    let merge = |(mut occ_analysis1, mut sketches1, _): Counts<'s>,
+                 (occ_analysis2, sketches2, _): Counts<'s>|
+     -> Counts<'s> {
+        for (n, entry2) in occ_analysis2.ngrams {
+            let entry1 = occ_analysis1.ngrams.entry(n).or_default();
+
+            *entry1 += entry2;
+        }
+
+        for (n, entry2) in occ_analysis2.skipgrams {
+            let entry1 = occ_analysis1.skipgrams.entry(n).or_default();
+
+            *entry1 += entry2;
+        }
+
+        for (shape, entry2) in occ_analysis2.shaped_skipgrams {
+            let entry1 = occ_analysis1.shaped_skipgrams.entry(shape).or_default();
+
+            *entry1 += entry2;
+        }
+
+        occ_analysis1.words += occ_analysis2.words;
+
+        for (n, entry2) in occ_analysis2.word_ngrams {
+            let entry1 = occ_analysis1.word_ngrams.entry(n).or_default();
+
+            *entry1 += entry2;
+        }
+
+        occ_analysis1.positions += occ_analysis2.positions;
+        occ_analysis1.lengths += occ_analysis2.lengths;
+        occ_analysis1.num_sentences += occ_analysis2.num_sentences;
+
+        for (n, sketch2) in sketches2 {
+            if let Some(sketch1) = sketches1.get_mut(&n) {
+                sketch1.merge(sketch2);
+            }
+        }
+
+        (occ_analysis1, sketches1, None)
+    };
+
+    // With pruning, tables are pruned every chunk of sentences to keep them bounded
+    let (mut occ_analysis, sketches, _) = if pruning.is_some() || word_ngram_pruning.is_some() {
+        sentences
+            .par_iter()
+            .enumerate()
+            .fold_chunks(PRUNING_CHUNK, &new_counts, &count)
+            .map(|(mut occ_analysis, sketches, previous)| {
+                prune_bounded(&mut occ_analysis);
+                (occ_analysis, sketches, previous)
+            })
+            .reduce(&new_counts, |counts1, counts2| {
+                let (mut occ_analysis, sketches, previous) = merge(counts1, counts2);
+                prune_bounded(&mut occ_analysis);
+                (occ_analysis, sketches, previous)
+            })
+    } else {
+        sentences
+            .par_iter()
+            .enumerate()
+            .fold(&new_counts, &count)
+            .reduce(&new_counts, &merge)
+    };
+
+    let mut approximations = Approximations::new();
+    for (n, sketch) in sketches {
+        let (occurances, bounds) = sketch.finish();
+        occ_analysis.ngrams.insert(n, occurances);
+        approximations.insert(n, bounds);
+    }
+    occ_analysis.ngrams.sort_keys();
+    occ_analysis.word_ngrams.sort_keys();
 
-                occ_analysis
-            },
-        )
-        .reduce(
-            || OccuranceAnalysis::default(),
-            |mut occ_analysis1: OccuranceAnalysis<usize>,
-             occ_analysis2: OccuranceAnalysis<usize>| {
-                for (n, entry2) in occ_analysis2.ngrams {
-                    let entry1 = occ_analysis1.ngrams.entry(n).or_default();
-
-                    *entry1 += entry2.clone();
-                }
+    if let Some(pruning) = pruning {
+        occ_analysis.prune(pruning.min_count, None);
+    }
+    if let Some(pruning) = word_ngram_pruning {
+        occ_analysis.prune_word_ngrams(pruning.min_count, None);
+    }
 
-                for (n, entry2) in occ_analysis2.skipgrams {
-                    let entry1 = occ_analysis1.skipgrams.entry(n).or_default();
+    occ_analysis.sort();
 
-                    *entry1 += entry2.clone();
-                }
+    // ngrams
+    (occ_analysis, approximations)
 
-                occ_analysis1.words += occ_analysis2.words;
-                occ_analysis1.num_sentences += occ_analysis2.num_sentences;
+    // ngrams
+}
 
-                occ_analysis1
-            },
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specification(ngram_ns: Vec<usize>, boundaries: Boundaries) -> AnalysisSpecification {
+        AnalysisSpecification {
+            ngram_ns,
+            skipgram_ns: vec![1],
+            boundaries,
+            segmentation: Segmentation::default(),
+            normalization: None,
+            words: WordTokenization::default(),
+            skipgram_shapes: Vec::new(),
+            pruning: None,
+            word_ngram_ns: Vec::new(),
+            word_ngram_pruning: None,
+            approximate: IndexMap::new(),
+            bootstrap_shards: None,
+            sampling: None,
+            deduplication: None,
+            language: None,
+            sources: None,
+        }
+    }
+
+    #[test]
+    fn test_analyse() {
+        let sentences = vec!["Insp".to_owned(), "Brn".to_owned(), "Su".to_owned()];
+
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![1, 2, 3], Boundaries::default()),
+            &WordTokenizer::default(),
+            false,
         );
 
-    occ_analysis.sort();
+        assert_eq!(analysis.ngrams[&1].get(&"n".into()), Some(&2));
+        assert_eq!(analysis.ngrams[&1].sum(), 9);
+        assert_eq!(analysis.ngrams[&2].sum(), 6);
+        assert_eq!(analysis.ngrams[&3].get(&"nsp".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&3].sum(), 3);
+        assert_eq!(analysis.skipgrams[&1].get(&"Bn".into()), Some(&1));
+        assert_eq!(analysis.skipgrams[&1].sum(), 3);
+        assert_eq!(analysis.num_sentences, 3);
+    }
 
-    // ngrams
-    occ_analysis
+    #[test]
+    fn test_analyse_skipgram_shapes() {
+        let sentences = vec!["abcd".to_owned()];
+        let mut specification = specification(vec![1], Boundaries::default());
+        specification.skipgram_shapes = ["a_b", "ab_c", "a__b"]
+            .iter()
+            .map(|shape| shape.parse().unwrap())
+            .collect();
+
+        let (analysis, _) = analyse(&sentences, &specification, &WordTokenizer::default(), false);
+        let shaped = &analysis.shaped_skipgrams;
+
+        assert_eq!(shaped["a_b"].get(&"ac".into()), Some(&1));
+        assert_eq!(shaped["a_b"].get(&"bd".into()), Some(&1));
+        assert_eq!(shaped["a_b"].sum(), 2);
+        assert_eq!(shaped["ab_c"].keys().collect::<Vec<_>>(), vec!["abd"]);
+        assert_eq!(shaped["a__b"].keys().collect::<Vec<_>>(), vec!["ad"]);
+        assert!("ab_".parse::<SkipgramShape>().is_err());
+        assert!("abc".parse::<SkipgramShape>().is_err());
+    }
 
-    // ngrams
-}
+    #[test]
+    fn test_analyse_pruning() {
+        let sentences = vec!["abab".to_owned(), "abcd".to_owned()];
+        let mut specification = specification(vec![1, 2, 8], Boundaries::default());
+        specification.pruning = Some(Pruning {
+            min_count: 2,
+            max_entries: 100,
+        });
+
+        let (analysis, _) = analyse(&sentences, &specification, &WordTokenizer::default(), false);
+
+        assert_eq!(analysis.ngrams[&2].get(&"ab".into()), Some(&3));
+        assert_eq!(analysis.ngrams[&2].get(&"cd".into()), None);
+        assert_eq!(analysis.ngrams[&8].len(), 0);
+        assert!(analysis.ngram_table(3).is_err());
+    }
+
+    #[test]
+    fn test_analyse_pruning_late_ngram() {
+        // Rare bigrams fill the table before a frequent one first occurs
+        let mut sentences: Vec<String> = ('a'..='z')
+            .flat_map(|c| [format!("{c}{c}"), format!("{c}{c}")])
+            .collect();
+        sentences.extend(std::iter::repeat_n("ø".repeat(2), 50));
+
+        let mut specification = specification(vec![2], Boundaries::default());
+        specification.pruning = Some(Pruning {
+            min_count: 2,
+            max_entries: 10,
+        });
 
-// #[cfg(test)]
-// mod tests {
-//     use std::collections::HashMap;
+        let (analysis, _) = analyse(&sentences, &specification, &WordTokenizer::default(), false);
 
-//     use super::*;
+        assert_eq!(analysis.ngrams[&2].get(&"øø".into()), Some(&50));
+        assert!(analysis.ngrams[&2].len() <= 10);
+    }
+
+    #[test]
+    fn test_analyse_positions() {
+        let sentences = vec!["hej du".to_owned(), "a".to_owned()];
 
-//     #[test]
-//     fn test_analyse() {
-//         let sentences = vec!["Insp".to_owned(), "Brn".to_owned(), "Su".to_owned()];
-//         let ngram_ns = vec![1, 2, 3];
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![1], Boundaries::default()),
+            &WordTokenizer::default(),
+            false,
+        );
+        let positions = &analysis.positions;
+
+        assert_eq!(positions.initial.get(&"h".into()), Some(&1));
+        assert_eq!(positions.initial.get(&"a".into()), Some(&1));
+        assert_eq!(positions.initial.sum(), 3);
+        assert_eq!(positions.medial.get(&"e".into()), Some(&1));
+        assert_eq!(positions.medial.sum(), 1);
+        assert_eq!(positions.final_.get(&"a".into()), Some(&1));
+        assert_eq!(positions.final_.sum(), 3);
+        assert_eq!(positions.indexed[&2].get(&"u".into()), Some(&1));
+        assert_eq!(positions.indexed[&3].get(&"j".into()), Some(&1));
+        assert_eq!(positions.indexed.len(), 3);
+
+        let lengths = analysis.lengths.summary();
+        assert_eq!(lengths.word_units.mean, 2.0);
+        assert_eq!(lengths.sentence_words.percentiles[&99], 2);
+        assert_eq!(lengths.sentence_units.mean, 3.5);
+    }
 
-//         let mut known_ngrams = NGramLike::<usize>::new();
+    #[test]
+    fn test_analyse_word_ngrams() {
+        let sentences = vec![
+            "the cat sat".to_owned(),
+            "the cat ran".to_owned(),
+            "a dog".to_owned(),
+        ];
+        let mut specification = specification(vec![1], Boundaries::default());
+        specification.word_ngram_ns = vec![2, 3];
+        specification.word_ngram_pruning = Some(Pruning {
+            min_count: 2,
+            max_entries: 100,
+        });
+
+        let (analysis, _) = analyse(&sentences, &specification, &WordTokenizer::default(), false);
+
+        let bigrams = &analysis.word_ngrams[&2];
+        assert_eq!(bigrams.get(&"the cat".into()), Some(&2));
+        assert_eq!(bigrams.len(), 1);
+        assert!(analysis.word_ngrams[&3].is_empty());
+    }
 
-//         let mut known_ngrams_1 = IndexMap::new();
-//         known_ngrams_1.insert("n".to_owned(), 2);
-//         known_ngrams_1.insert("r".to_owned(), 1);
-//         known_ngrams_1.insert("s".to_owned(), 1);
-//         known_ngrams_1.insert("p".to_owned(), 1);
-//         known_ngrams_1.insert("I".to_owned(), 1);
-//         known_ngrams_1.insert("S".to_owned(), 1);
-//         known_ngrams_1.insert("u".to_owned(), 1);
-//         known_ngrams_1.insert("B".to_owned(), 1);
+    #[test]
+    fn test_analyse_boundaries() {
+        let sentences = vec!["ab c".to_owned(), "d".to_owned()];
+        let boundaries = Boundaries {
+            sentence: true,
+            word: true,
+            span_sentences: false,
+        };
+
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![1, 2], boundaries),
+            &WordTokenizer::default(),
+            false,
+        );
+        let bigrams = &analysis.ngrams[&2];
+
+        assert_eq!(bigrams.get(&"␂⟨".into()), Some(&2));
+        assert_eq!(bigrams.get(&"⟨a".into()), Some(&1));
+        assert_eq!(bigrams.get(&"b⟩".into()), Some(&1));
+        assert_eq!(bigrams.get(&"⟩ ".into()), Some(&1));
+        assert_eq!(bigrams.get(&"⟩␃".into()), Some(&2));
+        assert_eq!(analysis.ngrams[&1].get(&"⟨".into()), Some(&3));
+        assert_eq!(analysis.words.sum(), 3);
+    }
 
-//         let mut known_ngrams_2 = IndexMap::new();
-//         known_ngrams_2.insert("In".to_owned(), 1);
-//         known_ngrams_2.insert("Br".to_owned(), 1);
-//         known_ngrams_2.insert("rn".to_owned(), 1);
-//         known_ngrams_2.insert("sp".to_owned(), 1);
-//         known_ngrams_2.insert("ns".to_owned(), 1);
-//         known_ngrams_2.insert("Su".to_owned(), 1);
+    #[test]
+    fn test_analyse_span_sentences() {
+        let sentences = vec!["ab".to_owned(), "cd".to_owned()];
+        let boundaries = Boundaries {
+            span_sentences: true,
+            ..Default::default()
+        };
+
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![1, 2, 3], boundaries.clone()),
+            &WordTokenizer::default(),
+            false,
+        );
 
-//         let mut known_ngrams_3 = IndexMap::new();
-//         known_ngrams_3.insert("Brn".to_owned(), 1);
-//         known_ngrams_3.insert("nsp".to_owned(), 1);
-//         known_ngrams_3.insert("Ins".to_owned(), 1);
+        assert_eq!(analysis.ngrams[&1].sum(), 5);
+        assert_eq!(analysis.ngrams[&1].get(&" ".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&2].get(&"b ".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&2].get(&" c".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&2].sum(), 4);
+        assert_eq!(analysis.ngrams[&3].get(&"ab ".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&3].get(&"b c".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&3].get(&" cd".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&3].sum(), 3);
+        assert_eq!(analysis.skipgrams[&1].get(&"bc".into()), Some(&1));
+
+        // Windows span several sentences shorter than them
+        let sentences = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![5], boundaries.clone()),
+            &WordTokenizer::default(),
+            false,
+        );
 
-//         known_ngrams.insert(1 as u8, known_ngrams_1);
-//         known_ngrams.insert(2 as u8, known_ngrams_2);
-//         known_ngrams.insert(3 as u8, known_ngrams_3);
+        assert_eq!(analysis.ngrams[&5].get(&"a b c".into()), Some(&1));
+        assert_eq!(analysis.ngrams[&5].sum(), 1);
 
-//         let ngrams = analyse(&sentences, ngram_ns, false);
+        // Tails are carried across many sentences, counted in several runs
+        let sentences = vec!["ab".to_owned(); 5000];
+        let (analysis, _) = analyse(
+            &sentences,
+            &specification(vec![3], boundaries),
+            &WordTokenizer::default(),
+            false,
+        );
 
-//         assert_eq!(ngrams, known_ngrams);
-//     }
-// }
+        assert_eq!(analysis.ngrams[&3].get(&"ab ".into()), Some(&4999));
+        assert_eq!(analysis.ngrams[&3].get(&"b a".into()), Some(&4999));
+        assert_eq!(analysis.ngrams[&3].get(&" ab".into()), Some(&4999));
+        assert_eq!(analysis.ngrams[&3].sum(), 3 * 4999);
+    }
+}
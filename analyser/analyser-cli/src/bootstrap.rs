@@ -0,0 +1,208 @@
+use indexmap::IndexMap;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::occurance::{OccuranceAnalysis, Occurances};
+
+/// Parameters of the confidence intervals of the frequencies of a report.
+/// Intervals are estimated by resampling the shards of each source with replacement.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bootstrap {
+    /// Number of resampled corpora the intervals are estimated from
+    #[serde(default = "default_replicates")]
+    pub replicates: usize,
+
+    /// Probability of a frequency of a resampled corpus lying within its interval
+    #[serde(default = "default_confidence")]
+    pub confidence: f64,
+
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_replicates() -> usize {
+    200
+}
+
+fn default_confidence() -> f64 {
+    0.95
+}
+
+/// Confidence intervals of the frequencies of a report, by the same tables as the frequencies
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FrequencyIntervals {
+    pub bootstrap: Bootstrap,
+    pub lower: OccuranceAnalysis<f64>,
+    pub upper: OccuranceAnalysis<f64>,
+}
+
+/// Adds the shards of another analysis, shard by shard, for when its counts are added
+pub fn add_shards(
+    shards: &mut Vec<OccuranceAnalysis<usize>>,
+    other: Vec<OccuranceAnalysis<usize>>,
+) {
+    if shards.len() < other.len() {
+        shards.resize_with(other.len(), Default::default);
+    }
+
+    for (shard, other) in shards.iter_mut().zip(other) {
+        *shard += other;
+    }
+}
+
+impl Bootstrap {
+    /// Estimates intervals of `frequencies` from the shards of each source along with its share of
+    /// the total weight. Every source must have at least one shard.
+    ///
+    /// Replicates are resampled a table at a time, so only the samples of the keys of one table
+    /// per thread are kept rather than every replicate in full.
+    pub fn intervals(
+        &self,
+        sources: &[(Vec<OccuranceAnalysis<usize>>, f64)],
+        frequencies: &OccuranceAnalysis<f64>,
+    ) -> FrequencyIntervals {
+        // How often each shard of each source is drawn, by replicate
+        let draws: Vec<Vec<Vec<usize>>> = (0..self.replicates.max(1))
+            .map(|replicate| {
+                let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+                rng.set_stream(replicate as u64);
+
+                sources
+                    .iter()
+                    .map(|(shards, _)| {
+                        let mut counts = vec![0; shards.len()];
+                        for _ in 0..shards.len() {
+                            counts[rng.gen_range(0..shards.len())] += 1;
+                        }
+                        counts
+                    })
+                    .collect()
+            })
+            .collect();
+        let shard_tables: Vec<Vec<IndexMap<String, &Occurances<usize>>>> = sources
+            .iter()
+            .map(|(shards, _)| shards.iter().map(|shard| shard.tables()).collect())
+            .collect();
+
+        let alpha = (1.0 - self.confidence) / 2.0;
+        let mut lower = frequencies.clone();
+        let mut upper = frequencies.clone();
+        lower.lengths = Default::default();
+        upper.lengths = Default::default();
+
+        let upper_tables: Vec<&mut Occurances<f64>> = upper.tables_mut().into_values().collect();
+        let tables: Vec<_> = lower.tables_mut().into_iter().zip(upper_tables).collect();
+        tables
+            .into_par_iter()
+            .for_each(|((name, lower_table), upper_table)| {
+                // The table of each shard along with the weight of its source and its weighted total
+                let weighted: Vec<Vec<_>> = shard_tables
+                    .iter()
+                    .zip(sources)
+                    .map(|(tables, (_, weight))| {
+                        tables
+                            .iter()
+                            .map(|tables| {
+                                let table = *tables.get(&name)?;
+                                Some((table, *weight, table.sum() as f64 * weight))
+                            })
+                            .collect()
+                    })
+                    .collect();
+
+                let mut samples = vec![Vec::with_capacity(draws.len()); lower_table.len()];
+                for counts in &draws {
+                    let mut total = 0.0;
+                    let mut resampled = vec![0.0; lower_table.len()];
+                    let drawn = weighted.iter().flatten().zip(counts.iter().flatten());
+                    for (shard, count) in drawn {
+                        let Some((table, weight, sum)) = shard else {
+                            continue;
+                        };
+                        if *count == 0 {
+                            continue;
+                        }
+
+                        total += sum * *count as f64;
+                        let keys = lower_table.iter().map(|(key, _)| key);
+                        for (key, value) in keys.zip(resampled.iter_mut()) {
+                            let occurances = table.get(key).copied().unwrap_or(0);
+                            *value += occurances as f64 * weight * *count as f64;
+                        }
+                    }
+
+                    for (value, samples) in resampled.into_iter().zip(samples.iter_mut()) {
+                        samples.push(if total > 0.0 { value / total } else { 0.0 });
+                    }
+                }
+
+                let upper_values = upper_table.iter_mut().map(|(_, value)| value);
+                let bounds = lower_table.iter_mut().zip(upper_values).zip(samples);
+                for (((_, low), high), mut samples) in bounds {
+                    samples.sort_by(f64::total_cmp);
+
+                    *low = quantile(&samples, alpha);
+                    *high = quantile(&samples, 1.0 - alpha);
+                }
+            });
+
+        FrequencyIntervals {
+            bootstrap: self.clone(),
+            lower,
+            upper,
+        }
+    }
+}
+
+/// Linearly interpolated quantile `p` of sorted, non-empty samples
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard(counts: &[(&str, usize)]) -> OccuranceAnalysis<usize> {
+        let mut shard = OccuranceAnalysis::<usize>::default();
+        let bigrams = shard.ngrams.entry(2).or_default();
+        for (bigram, count) in counts {
+            bigrams.increment((*bigram).into(), *count);
+        }
+        shard
+    }
+
+    #[test]
+    fn test_bootstrap_intervals() {
+        let shards = vec![
+            shard(&[("th", 10), ("he", 10)]),
+            shard(&[("th", 12), ("he", 8)]),
+            shard(&[("th", 8), ("he", 12)]),
+            shard(&[("th", 10), ("he", 10)]),
+        ];
+        let mut frequencies = OccuranceAnalysis::<f64>::default();
+        for shard in shards.clone() {
+            frequencies += shard * 1.0;
+        }
+        frequencies.normalize();
+
+        let bootstrap = Bootstrap {
+            replicates: 100,
+            confidence: 0.9,
+            seed: 1,
+        };
+        let intervals = bootstrap.intervals(&[(shards, 1.0)], &frequencies);
+
+        let th =
+            |analysis: &OccuranceAnalysis<f64>| *analysis.ngrams[&2].get(&"th".into()).unwrap();
+        assert!(th(&intervals.lower) <= 0.5 && th(&intervals.upper) >= 0.5);
+        assert!(th(&intervals.lower) >= 0.4 && th(&intervals.upper) <= 0.6);
+        assert!(th(&intervals.lower) < th(&intervals.upper));
+    }
+}
@@ -1,19 +1,43 @@
 use crate::{
-    crypt::DigestExt,
-    io::get_mtime,
-    objects::analysis::{Analysis, AnalysisMetadata, AnalysisSource},
-    utils::read_json,
+    bootstrap::add_shards,
+    crypt::{DigestExt, Sha256Reader},
+    filters::{
+        dedup::Deduplication,
+        language::{LanguageFilter, LanguageModel, MODEL_N},
+        sample::Sampling,
+    },
+    io::{collect_files, files_sha256, get_latest_mtime, get_mtime},
+    objects::{
+        analysis::{Analysis, AnalysisMetadata, AnalysisSource},
+        report::Report,
+    },
+    occurance::OccuranceAnalysis,
+    segmentation::{Normalization, Segmentation},
+    sketch::add_approximations,
+    sources::{
+        epub::Book,
+        git::{remote_url, resolve_revision, GitLog},
+        keylog::{is_keylog, key_sequences, parse_keylog},
+        markup::{extract_text, MarkupFormat},
+        split_sentences,
+        wet::{QualityFilter, WarcReader},
+        wortschatz::{read_sentence_sources, SourcePartition, SourceSelection},
+    },
+    utils::{find_object, read_json, write_object, StorageFormat},
+    words::WordTokenizer,
 };
 use chrono::{self};
 use data_encoding::HEXUPPER;
-use eyre::{eyre, Result};
+use eyre::{eyre, Result, WrapErr};
 use humantime::format_duration;
+use indexmap::IndexMap;
 use rayon::prelude::*;
 use ring::digest::Digest;
 use std::{
-    fs::{read_to_string, File},
-    io::{BufWriter, Write},
-    path::Path,
+    collections::{BTreeMap, HashMap},
+    fs::{create_dir_all, read_to_string},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
     time::Instant,
 };
 use url::Url;
@@ -24,15 +48,98 @@ impl DigestExt for Digest {
     }
 }
 
-use crate::{analyse::analyse, io::file_sha256};
+use crate::{
+    analyse::{analyse, analyse_keys, AnalysisSpecification, Boundaries, Pruning, SkipgramShape},
+    io::file_sha256,
+};
+
+/// Options shared by all analysis sources
+pub struct AnalyseOptions {
+    pub ngram_n: usize,
+    pub skipgram_n: usize,
+    pub skipgram_shapes: Vec<SkipgramShape>,
+    pub pruning: Option<Pruning>,
+    /// Largest number of consecutive words counted as word ngrams
+    pub word_ngram_n: usize,
+    pub word_ngram_pruning: Option<Pruning>,
+    pub approximate: IndexMap<usize, usize>,
+    pub bootstrap_shards: Option<usize>,
+    pub sampling: Option<Sampling>,
+    pub deduplication: Option<Deduplication>,
+    pub language_filter: Option<LanguageFilter>,
+    pub boundaries: Boundaries,
+    pub segmentation: Segmentation,
+    pub normalization: Option<Normalization>,
+    pub word_tokenizer: WordTokenizer,
+    pub show_progress: bool,
+    pub force: bool,
+    /// Add to the existing analysis of an ID rather than replacing it
+    pub append: bool,
+    pub storage_format: StorageFormat,
+}
+
+impl AnalyseOptions {
+    pub fn specification(&self) -> AnalysisSpecification {
+        AnalysisSpecification {
+            ngram_ns: (1..=self.ngram_n).collect(),
+            skipgram_ns: (1..=self.skipgram_n).collect(),
+            boundaries: self.boundaries.clone(),
+            segmentation: self.segmentation,
+            normalization: self.normalization,
+            words: self.word_tokenizer.tokenization(),
+            skipgram_shapes: self.skipgram_shapes.clone(),
+            pruning: self.pruning.clone(),
+            word_ngram_ns: (2..=self.word_ngram_n).collect(),
+            word_ngram_pruning: self.word_ngram_pruning.clone(),
+            approximate: self.approximate.clone(),
+            bootstrap_shards: self.bootstrap_shards,
+            sampling: self.sampling.clone(),
+            deduplication: self.deduplication.clone(),
+            language: self
+                .language_filter
+                .as_ref()
+                .map(|filter| filter.identification()),
+            sources: None,
+        }
+    }
+}
+
+/// Builds a filter keeping sentences of `language`, telling languages apart by the ngram counts
+/// of the reports of `language` and `other_languages`
+pub fn language_filter(
+    language: &str,
+    other_languages: &[String],
+    working_directory: &Path,
+) -> Result<LanguageFilter> {
+    if other_languages.is_empty() {
+        return Err(eyre!(
+            "Cannot filter sentences by language without other languages to tell '{language}' apart from. Give them with --other-language"
+        ));
+    }
+
+    let models = [language.to_owned()]
+        .iter()
+        .chain(other_languages)
+        .map(|id| {
+            let report = Report::from_id(id, working_directory)
+                .wrap_err_with(|| format!("Error reading report '{id}' for language model"))?;
+            let ngrams = report.analysis_counts.ngram_table(MODEL_N)?;
+
+            Ok(LanguageModel::from_occurances(id, ngrams))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(LanguageFilter { target: 0, models })
+}
 
+/// Analyses a fetched Wortschatz corpus. Sentences may be selected by their sources, and the
+/// corpus split into an analysis for each partition, stored under `{id}@{partition}`.
 pub fn wortschatz(
     id: &str,
+    selection: &SourceSelection,
+    partition: Option<SourcePartition>,
     working_directory: &Path,
-    ngram_n: usize,
-    skipgram_n: usize,
-    show_progress: bool,
-    force: bool,
+    options: &AnalyseOptions,
 ) -> Result<()> {
     let start_time = Instant::now();
     println!("Analysing corpus: '{id}'...");
@@ -51,57 +158,711 @@ pub fn wortschatz(
         ));
     }
 
-    let mut analysis_path = working_directory.to_owned();
-    analysis_path.push("analysis");
-    analysis_path.push(format!("{}.json", &id));
-
-    let existing_sha256: Result<String> =
-        (|| Ok(read_json::<Analysis>(&analysis_path)?.source.hash))();
+    let analysis_path = get_analysis_path(id, working_directory);
 
     // Compute SHA256 of sentence file
     let sha256 = file_sha256(&sentences_path)?;
 
-    if !force && Some(sha256.to_str()) == existing_sha256.ok() {
+    let mut specification = options.specification();
+    specification.sources = (!selection.is_empty()).then(|| selection.clone());
+
+    if partition.is_none()
+        && !options.force
+        && is_analysed(&analysis_path, &sha256.to_str(), &specification)
+    {
         println!("Corpus was already analysed.");
         return Ok(());
     }
 
-    // Load sentences
+    // Load sentences, keeping their ids to link them to their sources
     let raw_sentences = read_to_string(&sentences_path)?;
-    let sentences: Vec<String> = raw_sentences
+    let sentences: Vec<(Option<u64>, String)> = raw_sentences
         .par_lines()
         .filter_map(|s| {
-            let (_, content) = s.split_once('\t')?;
-            Some(content.to_owned().to_lowercase())
+            let (sentence_id, content) = s.split_once('\t')?;
+            Some((
+                sentence_id.trim().parse().ok(),
+                content.to_owned().to_lowercase(),
+            ))
         })
         .collect();
 
-    let ngram_ns: Vec<usize> = (1..=ngram_n).collect();
-    let skipgram_ns: Vec<usize> = (1..=skipgram_n).collect();
-    let occurance_analysis = analyse(&sentences, ngram_ns, skipgram_ns, show_progress);
+    let sentence_sources = if partition.is_some() || !selection.is_empty() {
+        read_sentence_sources(sentences_path.parent().unwrap())?
+    } else {
+        HashMap::new()
+    };
+    let source_of = |sentence_id: Option<u64>| sentence_id.and_then(|id| sentence_sources.get(&id));
+
+    let sentences = sentences
+        .into_iter()
+        .filter(|(sentence_id, _)| selection.matches(source_of(*sentence_id)));
+
+    let Some(partition) = partition else {
+        let sentences = sentences.map(|(_, sentence)| sentence).collect();
+        let (occurance_analysis, shards, metadata) =
+            analyse_sentences(sentences, specification, options);
+
+        // Construct analysis
+        let analysis = Analysis {
+            source: wortschatz_source(&sentences_path, &sha256)?,
+            metadata,
+            analysis: occurance_analysis,
+            shards,
+        };
+
+        return store_analysis(analysis, &analysis_path, start_time, options);
+    };
+
+    // Sentences of unknown partition are left out
+    let mut partitions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (sentence_id, sentence) in sentences {
+        if let Some(key) = source_of(sentence_id).and_then(|source| partition.key(source)) {
+            partitions.entry(key).or_default().push(sentence);
+        }
+    }
+
+    for (key, sentences) in partitions {
+        let start_time = Instant::now();
+        let partition_id = format!("{id}@{key}");
+        let partition_path = get_analysis_path(&partition_id, working_directory);
+
+        let mut specification = specification.clone();
+        specification.sources = Some(partition.select(selection, &key));
+
+        if !options.force && is_analysed(&partition_path, &sha256.to_str(), &specification) {
+            println!("Partition '{partition_id}' was already analysed.");
+            continue;
+        }
+
+        println!("Analysing partition: '{partition_id}'...");
+        let (occurance_analysis, shards, metadata) =
+            analyse_sentences(sentences, specification, options);
+
+        let analysis = Analysis {
+            source: wortschatz_source(&sentences_path, &sha256)?,
+            metadata,
+            analysis: occurance_analysis,
+            shards,
+        };
+
+        store_analysis(analysis, &partition_path, start_time, options)?;
+    }
+
+    Ok(())
+}
+
+fn wortschatz_source(sentences_path: &Path, sha256: &Digest) -> Result<AnalysisSource> {
+    Ok(AnalysisSource {
+        hash: sha256.to_str(),
+        license: "CC BY-NC".to_owned(),
+        origin_id: "wortschatz".to_owned(),
+        origin_name: "Deutsche Wortschatz by Institut fűr Informatik at Universität Leipzig"
+            .to_owned(),
+        origin_url: Url::parse("https://wortschatz.uni-leipzig.de/en")?,
+        date: get_mtime(sentences_path)?,
+        title: None,
+        author: None,
+        revision: None,
+        hashes: Vec::new(),
+    })
+}
+
+pub fn markup(
+    id: &str,
+    paths: &[PathBuf],
+    format: Option<MarkupFormat>,
+    keep_code: bool,
+    working_directory: &Path,
+    options: &AnalyseOptions,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Analysing documents as: '{id}'...");
+
+    let origin_url = local_origin_url(&paths[0])?;
+    let paths = collect_files(paths, &|path| match format {
+        Some(format) => MarkupFormat::from_path(path) == Some(format),
+        None => MarkupFormat::from_path(path).is_some(),
+    })?;
+
+    if paths.is_empty() {
+        return Err(eyre!("Found no documents to analyse"));
+    }
+
+    let analysis_path = get_analysis_path(id, working_directory);
+    let sha256 = files_sha256(&paths)?;
+
+    let specification = options.specification();
+
+    if !options.force && is_analysed(&analysis_path, &sha256.to_str(), &specification) {
+        println!("Documents were already analysed.");
+        return Ok(());
+    }
+
+    // Extract sentences from the visible text of each document
+    let mut sentences: Vec<String> = Vec::new();
+    for path in &paths {
+        let document_format = format
+            .or_else(|| MarkupFormat::from_path(path))
+            .ok_or_else(|| eyre!("Could not determine format of '{}'", path.display()))?;
+
+        let content = read_to_string(path)?;
+        let text = extract_text(&content, document_format, keep_code);
+        sentences.extend(split_sentences(&text).into_iter().map(|s| s.to_lowercase()));
+    }
+
+    let (occurance_analysis, shards, metadata) =
+        analyse_sentences(sentences, specification, options);
+
+    let analysis = Analysis {
+        source: AnalysisSource {
+            hash: sha256.to_str(),
+            license: "Unknown".to_owned(),
+            origin_id: "markup".to_owned(),
+            origin_name: paths
+                .iter()
+                .filter_map(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            origin_url,
+            date: get_latest_mtime(&paths)?,
+            title: None,
+            author: None,
+            revision: None,
+            hashes: Vec::new(),
+        },
+        metadata,
+        analysis: occurance_analysis,
+        shards,
+    };
+
+    store_analysis(analysis, &analysis_path, start_time, options)
+}
+
+/// Analyses each EPUB book, storing the analysis under the file stem of the book
+pub fn epub(paths: &[PathBuf], working_directory: &Path, options: &AnalyseOptions) -> Result<()> {
+    let paths = collect_files(paths, &|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("epub"))
+    })?;
+
+    for path in paths {
+        let start_time = Instant::now();
+        let id = path
+            .file_stem()
+            .ok_or_else(|| eyre!("Invalid EPUB path '{}'", path.display()))?
+            .to_string_lossy();
+        println!("Analysing book: '{id}'...");
+
+        let analysis_path = get_analysis_path(&id, working_directory);
+        let sha256 = file_sha256(&path)?;
+
+        let specification = options.specification();
+
+        if !options.force && is_analysed(&analysis_path, &sha256.to_str(), &specification) {
+            println!("Book was already analysed.");
+            continue;
+        }
+
+        let book = Book::from_path(&path)?;
+        let sentences: Vec<String> = book
+            .chapters
+            .iter()
+            .flat_map(|chapter| split_sentences(chapter))
+            .map(|s| s.to_lowercase())
+            .collect();
+
+        let (occurance_analysis, shards, metadata) =
+            analyse_sentences(sentences, specification, options);
+
+        let analysis = Analysis {
+            source: AnalysisSource {
+                hash: sha256.to_str(),
+                license: book.rights.unwrap_or_else(|| "Unknown".to_owned()),
+                origin_id: "epub".to_owned(),
+                origin_name: book.title.clone().unwrap_or_else(|| id.to_string()),
+                origin_url: local_origin_url(&path)?,
+                date: get_mtime(&path)?,
+                title: book.title,
+                author: book.author,
+                revision: None,
+                hashes: Vec::new(),
+            },
+            metadata,
+            analysis: occurance_analysis,
+            shards,
+        };
+
+        store_analysis(analysis, &analysis_path, start_time, options)?;
+    }
+
+    Ok(())
+}
+
+/// Analyses text piped through standard input
+pub fn stdin(id: &str, working_directory: &Path, options: &AnalyseOptions) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Analysing standard input as: '{id}'...");
+
+    // Hash the stream while reading it
+    let mut reader = BufReader::new(Sha256Reader::new(io::stdin().lock()));
+    let mut sentences: Vec<String> = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        sentences.extend(split_sentences(&line).into_iter().map(|s| s.to_lowercase()));
+        line.clear();
+    }
+    let sha256 = reader.into_inner().finish();
+
+    let analysis_path = get_analysis_path(id, working_directory);
+
+    let specification = options.specification();
+
+    if !options.force && is_analysed(&analysis_path, &sha256.to_str(), &specification) {
+        println!("Input was already analysed.");
+        return Ok(());
+    }
+
+    let (occurance_analysis, shards, metadata) =
+        analyse_sentences(sentences, specification, options);
+
+    let analysis = Analysis {
+        source: AnalysisSource {
+            hash: sha256.to_str(),
+            license: "Unknown".to_owned(),
+            origin_id: "stdin".to_owned(),
+            origin_name: "Standard input".to_owned(),
+            origin_url: Url::parse("file:///dev/stdin")?,
+            date: chrono::Utc::now(),
+            title: None,
+            author: None,
+            revision: None,
+            hashes: Vec::new(),
+        },
+        metadata,
+        analysis: occurance_analysis,
+        shards,
+    };
+
+    store_analysis(analysis, &analysis_path, start_time, options)
+}
+
+/// Analyses WET files from Common Crawl, keeping only text that passes the quality filter
+pub fn wet(
+    id: &str,
+    paths: &[PathBuf],
+    filter: &QualityFilter,
+    working_directory: &Path,
+    options: &AnalyseOptions,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Analysing WET files as: '{id}'...");
+
+    let paths = collect_files(paths, &|path| {
+        let name = path.to_string_lossy();
+        name.ends_with(".wet") || name.ends_with(".wet.gz")
+    })?;
+
+    if paths.is_empty() {
+        return Err(eyre!("Found no WET files to analyse"));
+    }
+
+    let analysis_path = get_analysis_path(id, working_directory);
+    let sha256 = files_sha256(&paths)?;
+
+    let specification = options.specification();
+
+    if !options.force && is_analysed(&analysis_path, &sha256.to_str(), &specification) {
+        println!("WET files were already analysed.");
+        return Ok(());
+    }
+
+    let mut sentences: Vec<String> = Vec::new();
+    let mut num_documents = 0;
+    let mut num_kept_documents = 0;
+
+    for path in &paths {
+        for record in WarcReader::from_path(path)? {
+            let record = record?;
+            if !record.is_conversion() {
+                continue;
+            }
+
+            num_documents += 1;
+            if let Some(lines) = filter.filter(&record) {
+                num_kept_documents += 1;
+                sentences.extend(
+                    lines
+                        .into_iter()
+                        .flat_map(split_sentences)
+                        .map(|s| s.to_lowercase()),
+                );
+            }
+        }
+    }
+
+    println!("Kept {num_kept_documents} of {num_documents} documents after quality filtering");
+
+    let (occurance_analysis, shards, metadata) =
+        analyse_sentences(sentences, specification, options);
 
-    // Construct analysis
     let analysis = Analysis {
         source: AnalysisSource {
             hash: sha256.to_str(),
-            license: "CC BY-NC".to_owned(),
-            origin_id: "wortschatz".to_owned(),
-            origin_name: "Deutsche Wortschatz by Institut fűr Informatik at Universität Leipzig"
-                .to_owned(),
-            origin_url: Url::parse("https://wortschatz.uni-leipzig.de/en")?,
-            date: get_mtime(&sentences_path)?,
+            license: "Common Crawl Terms of Use".to_owned(),
+            origin_id: "commoncrawl".to_owned(),
+            origin_name: "Common Crawl".to_owned(),
+            origin_url: Url::parse("https://commoncrawl.org")?,
+            date: get_latest_mtime(&paths)?,
+            title: None,
+            author: None,
+            revision: None,
+            hashes: Vec::new(),
+        },
+        metadata,
+        analysis: occurance_analysis,
+        shards,
+    };
+
+    store_analysis(analysis, &analysis_path, start_time, options)
+}
+
+/// Analyses the commit messages of a git repository, optionally including lines added in diffs
+pub fn git(
+    id: &str,
+    repository: &Path,
+    revision: &str,
+    include_diffs: bool,
+    excluded_authors: &[String],
+    working_directory: &Path,
+    options: &AnalyseOptions,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Analysing git repository as: '{id}'...");
+
+    let commit_hash = resolve_revision(repository, revision)?;
+    let log = GitLog::from_repository(repository, &commit_hash, include_diffs)?;
+
+    let analysis_path = get_analysis_path(id, working_directory);
+
+    let specification = options.specification();
+
+    if !options.force && is_analysed(&analysis_path, &log.sha256.to_str(), &specification) {
+        println!("Repository was already analysed.");
+        return Ok(());
+    }
+
+    let commits: Vec<_> = log
+        .commits
+        .iter()
+        .filter(|commit| !commit.is_bot(excluded_authors))
+        .collect();
+
+    println!(
+        "Kept {} of {} commits after excluding bots",
+        commits.len(),
+        log.commits.len()
+    );
+
+    let mut sentences: Vec<String> = Vec::new();
+    for commit in commits {
+        sentences.extend(commit.message_lines().flat_map(split_sentences));
+
+        if include_diffs {
+            sentences.extend(commit.added_lines.iter().flat_map(|l| split_sentences(l)));
+        }
+    }
+    let sentences: Vec<String> = sentences.into_iter().map(|s| s.to_lowercase()).collect();
+
+    let (occurance_analysis, shards, metadata) =
+        analyse_sentences(sentences, specification, options);
+
+    let repository = repository.canonicalize()?;
+    let origin_url = match remote_url(&repository) {
+        Some(url) => url,
+        None => local_origin_url(&repository)?,
+    };
+
+    let analysis = Analysis {
+        source: AnalysisSource {
+            hash: log.sha256.to_str(),
+            license: "Unknown".to_owned(),
+            origin_id: "git".to_owned(),
+            origin_name: repository
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| repository.display().to_string()),
+            origin_url,
+            date: chrono::Utc::now(),
+            title: None,
+            author: None,
+            revision: Some(commit_hash),
+            hashes: Vec::new(),
+        },
+        metadata,
+        analysis: occurance_analysis,
+        shards,
+    };
+
+    store_analysis(analysis, &analysis_path, start_time, options)
+}
+
+/// Analyses keystroke logs, counting ngrams of key presses including non-character keys
+pub fn keylog(
+    id: &str,
+    paths: &[PathBuf],
+    max_pause: u64,
+    working_directory: &Path,
+    options: &AnalyseOptions,
+) -> Result<()> {
+    let start_time = Instant::now();
+    println!("Analysing keystroke logs as: '{id}'...");
+
+    let paths = collect_files(paths, &is_keylog)?;
+
+    if paths.is_empty() {
+        return Err(eyre!("Found no keystroke logs to analyse"));
+    }
+
+    let analysis_path = get_analysis_path(id, working_directory);
+    let sha256 = files_sha256(&paths)?;
+
+    // Word boundaries and windows spanning sentences only apply to text,
+    // as bursts of typing are not typed one after another
+    let mut specification = options.specification();
+    specification.boundaries.word = false;
+    specification.boundaries.span_sentences = false;
+
+    // Bursts of keys are counted as they are, so stages selecting sentences would not be applied
+    if specification.sampling.is_some()
+        || specification.deduplication.is_some()
+        || specification.language.is_some()
+        || specification.bootstrap_shards.is_some()
+    {
+        return Err(eyre!(
+            "Sampling, de-duplication, language filtering and bootstrap shards apply to sentences, not keystroke logs"
+        ));
+    }
+
+    if !options.force && is_analysed(&analysis_path, &sha256.to_str(), &specification) {
+        println!("Keystroke logs were already analysed.");
+        return Ok(());
+    }
+
+    let mut sequences = Vec::new();
+    for path in &paths {
+        let events = parse_keylog(&read_to_string(path)?)?;
+        sequences.extend(key_sequences(&events, max_pause));
+    }
+
+    let (occurance_analysis, approximations) =
+        analyse_keys(&sequences, &specification, options.show_progress);
+
+    let analysis = Analysis {
+        source: AnalysisSource {
+            hash: sha256.to_str(),
+            license: "Unknown".to_owned(),
+            origin_id: "keylog".to_owned(),
+            origin_name: "Keystroke log".to_owned(),
+            origin_url: local_origin_url(&paths[0])?,
+            date: get_latest_mtime(&paths)?,
+            title: None,
+            author: None,
+            revision: None,
+            hashes: Vec::new(),
         },
         metadata: AnalysisMetadata {
             date: chrono::Utc::now(),
+            specification: Some(specification),
+            approximations,
+            deduplication: None,
+            language: None,
         },
         analysis: occurance_analysis,
+        shards: Vec::new(),
     };
 
-    let analysis_file = File::create(&analysis_path)?;
-    let mut analysis_file_buf = BufWriter::new(analysis_file);
+    store_analysis(analysis, &analysis_path, start_time, options)
+}
 
-    serde_json::to_writer_pretty(&mut analysis_file_buf, &analysis)?;
-    analysis_file_buf.flush()?;
+/// Runs the filtering stages of the specification over the sentences and analyses them,
+/// along with their bootstrap shards if asked for
+fn analyse_sentences(
+    sentences: Vec<String>,
+    specification: AnalysisSpecification,
+    options: &AnalyseOptions,
+) -> (
+    OccuranceAnalysis<usize>,
+    Vec<OccuranceAnalysis<usize>>,
+    AnalysisMetadata,
+) {
+    let sentences = match &specification.sampling {
+        Some(sampling) => sampling.sample(sentences),
+        None => sentences,
+    };
+
+    let sentences = match &specification.normalization {
+        Some(normalization) => sentences
+            .par_iter()
+            .map(|sentence| normalization.normalize(sentence))
+            .collect(),
+        None => sentences,
+    };
+
+    let (sentences, deduplication) = match &specification.deduplication {
+        Some(deduplication) => {
+            let (sentences, stats) = deduplication.deduplicate(sentences);
+            (sentences, Some(stats))
+        }
+        None => (sentences, None),
+    };
+
+    let (sentences, language) = match &options.language_filter {
+        Some(filter) => {
+            let (sentences, stats) = filter.filter(sentences);
+            (sentences, Some(stats))
+        }
+        None => (sentences, None),
+    };
+
+    let (occurance_analysis, approximations) = analyse(
+        &sentences,
+        &specification,
+        &options.word_tokenizer,
+        options.show_progress,
+    );
+
+    // Shards are contiguous, such that ngrams spanning sentences mostly stay within one.
+    // With fewer sentences than shards, there are fewer shards rather than empty ones,
+    // as empty shards would narrow the intervals resampled from them.
+    let shards = match specification.bootstrap_shards {
+        Some(num_shards) => {
+            let shard_len = sentences.len().div_ceil(num_shards.max(1)).max(1);
+            sentences
+                .chunks(shard_len)
+                .map(|shard| analyse(shard, &specification, &options.word_tokenizer, false).0)
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let metadata = AnalysisMetadata {
+        date: chrono::Utc::now(),
+        specification: Some(specification),
+        approximations,
+        deduplication,
+        language,
+    };
+
+    (occurance_analysis, shards, metadata)
+}
+
+pub fn get_analysis_path(id: &str, working_directory: &Path) -> PathBuf {
+    find_object(&working_directory.join("analysis"), id)
+}
+
+/// Checks whether an analysis already exists counting only the input with the given hash, with
+/// the same specification. Analyses the input was appended to count more and are analysed again,
+/// while appending it to them again is left to `append`.
+fn is_analysed(analysis_path: &Path, sha256: &str, specification: &AnalysisSpecification) -> bool {
+    let existing: Result<Analysis> = read_json(analysis_path);
+
+    existing.is_ok_and(|analysis| {
+        ingested_hashes(&analysis.source) == [sha256]
+            && analysis.metadata.specification.as_ref() == Some(specification)
+    })
+}
+
+/// Hashes of all inputs counted in an analysis
+pub fn ingested_hashes(source: &AnalysisSource) -> Vec<&str> {
+    if source.hashes.is_empty() {
+        vec![source.hash.as_str()]
+    } else {
+        source.hashes.iter().map(|hash| hash.as_str()).collect()
+    }
+}
+
+/// Adds the counts of the existing analysis at `analysis_path` to `analysis`, such that only
+/// new input needs to be analysed. Returns false if the input was counted in it already,
+/// leaving nothing to store.
+fn append_existing(analysis: &mut Analysis, analysis_path: &Path) -> Result<bool> {
+    if !analysis_path.exists() {
+        return Ok(true);
+    }
+
+    let existing: Analysis = read_json(analysis_path)?;
+    append(analysis, existing)
+        .wrap_err_with(|| format!("Cannot append to '{}'", analysis_path.display()))
+}
+
+fn append(analysis: &mut Analysis, existing: Analysis) -> Result<bool> {
+    if existing.metadata.specification.is_none() {
+        return Err(eyre!(
+            "The existing analysis was made before its settings were recorded"
+        ));
+    }
+    if existing.metadata.specification != analysis.metadata.specification {
+        return Err(eyre!(
+            "The existing analysis was analysed with different settings"
+        ));
+    }
+
+    if ingested_hashes(&existing.source).contains(&analysis.source.hash.as_str()) {
+        return Ok(false);
+    }
+
+    let mut hashes: Vec<String> = ingested_hashes(&existing.source)
+        .into_iter()
+        .map(|hash| hash.to_owned())
+        .collect();
+    hashes.push(analysis.source.hash.clone());
+
+    analysis.source.hashes = hashes;
+    analysis.analysis += existing.analysis;
+    analysis.analysis.sort();
+    add_shards(&mut analysis.shards, existing.shards);
+    add_approximations(
+        &mut analysis.metadata.approximations,
+        existing.metadata.approximations,
+    );
+    if let Some(stats) = existing.metadata.deduplication {
+        *analysis
+            .metadata
+            .deduplication
+            .get_or_insert_with(Default::default) += stats;
+    }
+    if let Some(stats) = existing.metadata.language {
+        *analysis
+            .metadata
+            .language
+            .get_or_insert_with(Default::default) += stats;
+    }
+
+    Ok(true)
+}
+
+fn local_origin_url(path: &Path) -> Result<Url> {
+    let path = path.canonicalize()?;
+    Url::from_file_path(&path).map_err(|_| eyre!("Could not make URL of '{}'", path.display()))
+}
+
+fn store_analysis(
+    mut analysis: Analysis,
+    analysis_path: &Path,
+    start_time: Instant,
+    options: &AnalyseOptions,
+) -> Result<()> {
+    if options.append && !append_existing(&mut analysis, analysis_path)? {
+        println!(
+            "Input was counted in '{}' already.",
+            analysis_path.display()
+        );
+        return Ok(());
+    }
+
+    create_dir_all(analysis_path.parent().unwrap())?;
+
+    let analysis_path = write_object(analysis_path, &analysis, options.storage_format)?;
 
     let analysis_stats_strs_sentences_words = vec![
         format!("{} sentences", analysis.analysis.num_sentences),
@@ -143,3 +904,51 @@ pub fn wortschatz(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append() {
+        let mut analysis = Analysis::for_test("b", 2);
+        assert!(append(&mut analysis, Analysis::for_test("a", 1)).unwrap());
+        assert_eq!(analysis.source.hashes, vec!["a", "b"]);
+        assert_eq!(analysis.analysis.num_sentences, 3);
+
+        // Appending the same input again changes nothing
+        let mut again = Analysis::for_test("b", 2);
+        assert!(!append(&mut again, analysis).unwrap());
+
+        let mut other_settings = Analysis::for_test("c", 1);
+        other_settings
+            .metadata
+            .specification
+            .as_mut()
+            .unwrap()
+            .ngram_ns = vec![1, 2];
+        assert!(append(&mut other_settings, Analysis::for_test("a", 1)).is_err());
+    }
+
+    #[test]
+    fn test_is_analysed() {
+        let directory = std::env::temp_dir().join(format!("is-analysed-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        let path = directory.join("test.json");
+
+        let single = Analysis::for_test("a", 1);
+        let specification = single.metadata.specification.clone().unwrap();
+        write_object(&path, &single, StorageFormat::Json).unwrap();
+        assert!(is_analysed(&path, "a", &specification));
+        assert!(!is_analysed(&path, "b", &specification));
+
+        // An analysis the input was appended to counts more than the input
+        let mut appended = Analysis::for_test("b", 1);
+        append(&mut appended, single).unwrap();
+        write_object(&path, &appended, StorageFormat::Json).unwrap();
+        assert!(!is_analysed(&path, "a", &specification));
+        assert!(!is_analysed(&path, "b", &specification));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
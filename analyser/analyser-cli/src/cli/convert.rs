@@ -0,0 +1,54 @@
+use std::{fs, path::Path};
+
+use clap::ValueEnum;
+use eyre::{Result, WrapErr};
+
+use super::{analyse::get_analysis_path, report::get_report_path};
+use crate::{
+    objects::{analysis::Analysis, report::Report},
+    utils::{read_json, write_object, StorageFormat},
+};
+
+/// Kind of object stored in the working directory
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum StoredObject {
+    Analysis,
+    Report,
+}
+
+/// Rewrites stored objects in another format, replacing them with files of its extension
+pub fn convert(
+    kind: StoredObject,
+    ids: &[String],
+    working_directory: &Path,
+    storage_format: StorageFormat,
+) -> Result<()> {
+    for id in ids {
+        let path = match kind {
+            StoredObject::Analysis => get_analysis_path(id, working_directory),
+            StoredObject::Report => get_report_path(id, working_directory),
+        };
+        let size_before = fs::metadata(&path)
+            .wrap_err_with(|| format!("Could not find {kind:?} '{id}'"))?
+            .len();
+
+        let new_path = match kind {
+            StoredObject::Analysis => {
+                let analysis: Analysis = read_json(&path)?;
+                write_object(&path, &analysis, storage_format)?
+            }
+            StoredObject::Report => {
+                let report = Report::from_path(&path)?;
+                write_object(&path, &report, storage_format)?
+            }
+        };
+
+        println!(
+            "Converted {kind:?} '{id}' to {storage_format:?} in {}, from {size_before} to {} bytes",
+            new_path.display(),
+            fs::metadata(&new_path)?.len()
+        );
+    }
+
+    Ok(())
+}
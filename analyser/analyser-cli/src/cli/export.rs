@@ -1,15 +1,19 @@
-use eyre::Result;
+use eyre::{Result, WrapErr};
 use std::{
     fs::{create_dir_all, File},
     io::Write,
     path::{Path, PathBuf},
 };
 
-use crate::objects::{export::OxeylyserLanguageData, report::Report};
+use crate::objects::{
+    export::{OxeylyserIntervals, OxeylyserLanguageData},
+    report::Report,
+};
 
 pub fn export_oxeylyzer(id: &str, working_directory: &Path, force: bool) -> Result<()> {
     let report = Report::from_id(id, working_directory)?;
-    let oxey_output = OxeylyserLanguageData::from_report(&report);
+    let oxey_output = OxeylyserLanguageData::from_report(&report)
+        .wrap_err_with(|| format!("Cannot export report '{id}' for oxeylyzer"))?;
 
     let mut export_path: PathBuf = working_directory.to_owned();
     export_path.push("export");
@@ -23,15 +27,34 @@ pub fn export_oxeylyzer(id: &str, working_directory: &Path, force: bool) -> Resu
     let export_file = File::create(&export_path)?;
     serde_json::to_writer_pretty(&export_file, &oxey_output)?;
 
+    // Intervals go to a file of their own, as oxeylyzer does not know them
+    if let Some(intervals) = OxeylyserIntervals::from_report(&report) {
+        let mut intervals_path: PathBuf = working_directory.to_owned();
+        intervals_path.push("export");
+        intervals_path.push("oxeylyzer");
+        intervals_path.push("intervals");
+        create_dir_all(&intervals_path)?;
+        intervals_path.push(format!("{id}.json"));
+
+        let intervals_file = File::create(&intervals_path)?;
+        serde_json::to_writer_pretty(&intervals_file, &intervals)?;
+    }
+
     Ok(())
 }
 
 pub fn export_cmini(id: &str, working_directory: &Path) -> Result<()> {
     let report = Report::from_id(id, working_directory)?;
 
-    let monograms = &report.analysis_counts.ngrams[&1];
-    let bigrams = &report.analysis_counts.ngrams[&2];
-    let trigrams = &report.analysis_counts.ngrams[&3];
+    let ngrams = |n| {
+        report
+            .analysis_counts
+            .ngram_table(n)
+            .wrap_err_with(|| format!("Cannot export report '{id}' for cmini"))
+    };
+    let monograms = ngrams(1)?;
+    let bigrams = ngrams(2)?;
+    let trigrams = ngrams(3)?;
     let words = &report.analysis_counts.words;
 
     let mut export_path: PathBuf = working_directory.to_owned();
@@ -28,7 +28,18 @@ pub fn wortschatz(id: &str, working_directory: &Path, force: bool) -> Result<()>
 
     _ = create_dir_all(sentences_path.parent().unwrap());
 
-    download_and_decompress_archive(url, sentences_path.as_path())?;
+    // Sentences are linked to the sources they were found in by the inverse sources file
+    let sources_path = sentences_path.with_file_name("sources.txt");
+    let inverse_sources_path = sentences_path.with_file_name("inv_so.txt");
+
+    download_and_decompress_archive(
+        url,
+        &[
+            ("-sentences.txt", &sentences_path),
+            ("-sources.txt", &sources_path),
+            ("-inv_so.txt", &inverse_sources_path),
+        ],
+    )?;
 
     println!(
         "Fetched Wortschatz Corpus '{}' and stored at '{}",
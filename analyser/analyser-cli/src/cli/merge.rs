@@ -0,0 +1,188 @@
+use std::{fs::create_dir_all, path::Path};
+
+use eyre::{eyre, Result, WrapErr};
+use url::Url;
+
+use super::analyse::{get_analysis_path, ingested_hashes};
+use crate::{
+    bootstrap::add_shards,
+    crypt::{digest_sha256, DigestExt},
+    filters::{dedup::DeduplicationStats, language::LanguageStats},
+    objects::analysis::{Analysis, AnalysisMetadata, AnalysisSource},
+    occurance::OccuranceAnalysis,
+    sketch::{add_approximations, Approximations},
+    utils::{read_json, write_object, StorageFormat},
+};
+
+/// Sums the analyses of shards of a corpus into a single analysis
+pub fn merge(
+    id: &str,
+    shard_ids: &[String],
+    working_directory: &Path,
+    force: bool,
+    storage_format: StorageFormat,
+) -> Result<()> {
+    let analysis_path = get_analysis_path(id, working_directory);
+    if !force && analysis_path.exists() {
+        println!("Analysis '{id}' exists already. Use --force to replace it.");
+        return Ok(());
+    }
+
+    let shards: Vec<Analysis> = shard_ids
+        .iter()
+        .map(|shard_id| {
+            read_json(&get_analysis_path(shard_id, working_directory))
+                .wrap_err_with(|| format!("Error reading analysis for ID '{shard_id}'"))
+        })
+        .collect::<Result<_>>()?;
+
+    // Validate shards before counting anything
+    let hashes = validate_shards(shard_ids, &shards)?;
+    let first = &shards[0];
+    let specification = first.metadata.specification.clone();
+
+    // Provenance shared by all shards is kept
+    let shared = |field: fn(&AnalysisSource) -> Option<&str>| -> Option<String> {
+        let value = field(&first.source)?;
+        shards
+            .iter()
+            .all(|shard| field(&shard.source) == Some(value))
+            .then(|| value.to_owned())
+    };
+
+    let origin_url = match shared(|source| Some(source.origin_url.as_str())) {
+        Some(url) => Url::parse(&url)?,
+        None => {
+            let analysis_directory = analysis_path.parent().unwrap().canonicalize()?;
+            Url::from_directory_path(&analysis_directory)
+                .map_err(|_| eyre!("Could not make URL of '{}'", analysis_directory.display()))?
+        }
+    };
+
+    let mut licenses: Vec<&str> = shards.iter().map(|s| s.source.license.as_str()).collect();
+    licenses.sort();
+    licenses.dedup();
+
+    let source = AnalysisSource {
+        origin_id: shared(|source| Some(&source.origin_id)).unwrap_or("merge".to_owned()),
+        origin_name: shared(|source| Some(&source.origin_name))
+            .unwrap_or_else(|| format!("Merge of {}", shard_ids.join(", "))),
+        origin_url,
+        license: licenses.join("; "),
+        date: shards.iter().map(|s| s.source.date).max().unwrap(),
+        hash: digest_sha256(hashes.join("\n").as_bytes())?.to_str(),
+        title: shared(|source| source.title.as_deref()),
+        author: shared(|source| source.author.as_deref()),
+        revision: shared(|source| source.revision.as_deref()),
+        hashes,
+    };
+
+    let mut occurance_analysis = OccuranceAnalysis::<usize>::default();
+    let mut bootstrap_shards = Vec::new();
+    let mut approximations = Approximations::new();
+    let mut deduplication: Option<DeduplicationStats> = None;
+    let mut language: Option<LanguageStats> = None;
+    for shard in shards {
+        occurance_analysis += shard.analysis;
+        add_shards(&mut bootstrap_shards, shard.shards);
+        add_approximations(&mut approximations, shard.metadata.approximations);
+
+        // Duplicates across shards are not detected, only those within each
+        if let Some(stats) = shard.metadata.deduplication {
+            *deduplication.get_or_insert_with(Default::default) += stats;
+        }
+        if let Some(stats) = shard.metadata.language {
+            *language.get_or_insert_with(Default::default) += stats;
+        }
+    }
+    occurance_analysis.sort();
+
+    let analysis = Analysis {
+        source,
+        metadata: AnalysisMetadata {
+            date: chrono::Utc::now(),
+            specification,
+            approximations,
+            deduplication,
+            language,
+        },
+        analysis: occurance_analysis,
+        shards: bootstrap_shards,
+    };
+
+    create_dir_all(analysis_path.parent().unwrap())?;
+    let analysis_path = write_object(&analysis_path, &analysis, storage_format)?;
+
+    println!(
+        "Merged {} analyses with {} sentences. Analysis stored in {}",
+        shard_ids.len(),
+        analysis.analysis.num_sentences,
+        analysis_path.display()
+    );
+
+    Ok(())
+}
+
+/// Checks that shards were analysed with the same, known settings and count distinct inputs.
+/// Returns the hashes of all inputs.
+fn validate_shards(shard_ids: &[String], shards: &[Analysis]) -> Result<Vec<String>> {
+    let Some(first) = shards.first() else {
+        return Err(eyre!("No analyses to merge"));
+    };
+
+    let mut hashes: Vec<String> = Vec::new();
+    for (shard_id, shard) in shard_ids.iter().zip(shards) {
+        if shard.metadata.specification.is_none() {
+            return Err(eyre!(
+                "Cannot merge '{shard_id}', as it was analysed before its settings were recorded. Analyse it again"
+            ));
+        }
+        if shard.metadata.specification != first.metadata.specification {
+            return Err(eyre!(
+                "Cannot merge '{shard_id}' with '{}', as they were analysed with different settings",
+                shard_ids[0]
+            ));
+        }
+
+        for hash in ingested_hashes(&shard.source) {
+            if hashes.iter().any(|h| h == hash) {
+                return Err(eyre!(
+                    "Cannot merge '{shard_id}', as its input '{hash}' is counted in another analysis already"
+                ));
+            }
+            hashes.push(hash.to_owned());
+        }
+    }
+
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_shards() {
+        let ids: Vec<String> = ["a", "b"].map(String::from).to_vec();
+        let valid = [Analysis::for_test("a", 1), Analysis::for_test("b", 1)];
+        assert_eq!(validate_shards(&ids, &valid).unwrap(), vec!["a", "b"]);
+
+        let duplicate = [Analysis::for_test("a", 1), Analysis::for_test("a", 1)];
+        let error = validate_shards(&ids, &duplicate).unwrap_err();
+        assert!(error.to_string().contains("counted in another analysis"));
+
+        let mut mismatched = [Analysis::for_test("a", 1), Analysis::for_test("b", 1)];
+        mismatched[1]
+            .metadata
+            .specification
+            .as_mut()
+            .unwrap()
+            .ngram_ns = vec![1, 2];
+        let error = validate_shards(&ids, &mismatched).unwrap_err();
+        assert!(error.to_string().contains("different settings"));
+
+        let mut legacy = [Analysis::for_test("a", 1), Analysis::for_test("b", 1)];
+        legacy[1].metadata.specification = None;
+        assert!(validate_shards(&ids, &legacy).is_err());
+    }
+}
@@ -3,9 +3,26 @@ use std::{env, fs::create_dir_all, path::PathBuf};
 use clap::{Args, Parser, Subcommand};
 use eyre::Result;
 
+use crate::{
+    analyse::{Boundaries, Pruning, SkipgramShape},
+    filters::{dedup::Deduplication, sample::Sampling},
+    segmentation::{Normalization, Segmentation},
+    sources::{
+        markup::MarkupFormat,
+        wet::QualityFilter,
+        wortschatz::{SourcePartition, SourceSelection},
+    },
+    utils::StorageFormat,
+    words::{Dictionary, WordSegmentation, WordTokenizer},
+};
+use convert::StoredObject;
+
 mod analyse;
+mod convert;
 mod export;
 mod fetch;
+mod merge;
+mod query;
 mod report;
 
 fn get_default_working_directory() -> PathBuf {
@@ -26,6 +43,11 @@ pub struct Cli {
         default_value = get_default_working_directory().into_os_string()
     )]
     working_directory: PathBuf,
+
+    /// Format that analyses and reports are written in. They are read in any format
+    #[arg(global = true, long, value_enum, default_value_t = StorageFormat::Json)]
+    storage_format: StorageFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -36,12 +58,31 @@ enum Commands {
     Fetch(FetchArgs),
 
     #[command(arg_required_else_help = true)]
-    Analyse(AnalyseArgs),
+    Analyse(Box<AnalyseArgs>),
+
+    #[command(arg_required_else_help = true)]
+    Merge(MergeArgs),
 
     #[command(arg_required_else_help = true)]
     Report { id: String },
     #[command(arg_required_else_help = true)]
     Export(ExportArgs),
+
+    /// Show frequencies of a report, with confidence intervals if it has them
+    #[command(arg_required_else_help = true)]
+    Query(QueryArgs),
+
+    /// Rewrite stored analyses or reports in the format given by --storage-format
+    #[command(arg_required_else_help = true)]
+    Convert(ConvertArgs),
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    #[arg(value_enum)]
+    kind: StoredObject,
+
+    ids: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -74,21 +115,322 @@ struct AnalyseArgs {
     #[arg(short = 'k', long, default_value_t = 3, value_name = "K")]
     skipgram_n: usize,
 
+    /// Also count skipgrams of this shape, such as 'a_b', 'a__b', 'ab_c' or 'a_bc'.
+    /// Underscores are skipped positions. Each shape is stored in a table of its own
+    #[arg(long = "skipgram-shape", value_name = "SHAPE")]
+    skipgram_shapes: Vec<SkipgramShape>,
+
+    /// Drop ngrams and skipgrams occurring fewer times, pruning while counting.
+    /// Keeps large orders, such as `-n 8`, in memory at the cost of approximate counts for rare entries
+    #[arg(long, value_name = "COUNT")]
+    prune_below: Option<usize>,
+
+    /// Also count sequences of up to N consecutive words, from word pairs upwards
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    word_ngrams: usize,
+
+    /// Drop word ngrams occurring fewer times, pruning while counting
+    #[arg(long, value_name = "COUNT")]
+    prune_word_ngrams_below: Option<usize>,
+
+    /// Prune tables whenever they grow beyond this many entries
+    #[arg(long, default_value_t = 1_000_000, value_name = "ENTRIES")]
+    prune_table_size: usize,
+
+    /// Count ngrams of order N approximately, keeping only the ENTRIES most frequent.
+    /// Uses constant memory, with error bounds stored in the analysis metadata
+    #[arg(long, value_name = "N=ENTRIES", value_parser = parse_approximation)]
+    approximate: Vec<(usize, usize)>,
+
+    /// Mark the start and end of sentences in ngrams
+    #[arg(long, default_value_t = false)]
+    sentence_boundaries: bool,
+
+    /// Mark the start and end of words in ngrams
+    #[arg(long, default_value_t = false)]
+    word_boundaries: bool,
+
+    /// Count ngrams spanning from one sentence into the next
+    #[arg(long, default_value_t = false)]
+    span_sentences: bool,
+
+    /// Units that sentences are split into
+    #[arg(long, value_enum, default_value_t = Segmentation::Grapheme)]
+    segmentation: Segmentation,
+
+    /// Bring sentences into this Unicode normalisation form before splitting them into units
+    #[arg(long = "normalize", value_enum, value_name = "FORM")]
+    normalization: Option<Normalization>,
+
+    /// How sentences are split into words
+    #[arg(long, value_enum, default_value_t = WordSegmentation::Whitespace)]
+    word_segmentation: WordSegmentation,
+
+    /// Trim punctuation from the start and end of words
+    #[arg(long, default_value_t = false)]
+    trim_punctuation: bool,
+
+    /// Word list for the dictionary word segmentation, with one word per line
+    #[arg(
+        long,
+        value_name = "FILE",
+        required_if_eq("word_segmentation", "dictionary")
+    )]
+    dictionary: Option<PathBuf>,
+
     #[arg(short, long, default_value_t = true, value_name = "?")]
     show_progress: bool,
 
     #[arg(short, long, default_value_t = false, value_name = "?")]
     force: bool,
+
+    /// Also count SHARDS contiguous parts of the sentences separately, such that reports can
+    /// bootstrap confidence intervals of frequencies. Multiplies the size of the analysis.
+    /// Small inputs get fewer shards, one per sentence
+    #[arg(long, value_name = "SHARDS")]
+    bootstrap_shards: Option<usize>,
+
+    /// Count only the first N sentences
+    #[arg(long, value_name = "N", group = "sampling")]
+    sample_first: Option<usize>,
+
+    /// Count each sentence with this probability, greater than 0 and at most 1
+    #[arg(long, value_name = "FRACTION", group = "sampling", value_parser = parse_sample_fraction)]
+    sample_fraction: Option<f64>,
+
+    /// Count N sentences drawn uniformly at random
+    #[arg(long, value_name = "N", group = "sampling")]
+    sample_reservoir: Option<usize>,
+
+    /// Seed of random sampling. The same seed and input give the same sample
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Drop repeated sentences before counting
+    #[arg(long, default_value_t = false)]
+    dedup: bool,
+
+    /// Also drop sentences at least this similar to an earlier sentence, between 0 and 1.
+    /// Similarity is the estimated Jaccard similarity of their character shingles
+    #[arg(long, value_name = "SIMILARITY", value_parser = parse_similarity)]
+    dedup_near: Option<f64>,
+
+    /// Length of the character shingles compared to find near-duplicate sentences
+    #[arg(long, default_value_t = 5, value_name = "LEN", value_parser = parse_shingle_len)]
+    shingle_len: usize,
+
+    /// Keep only sentences identified as the language of this report rather than another.
+    /// Languages are told apart by the trigram counts of reports
+    #[arg(long, value_name = "REPORT", requires = "other_languages")]
+    language: Option<String>,
+
+    /// Reports of other languages that sentences may be identified as
+    #[arg(long = "other-language", value_name = "REPORT", requires = "language")]
+    other_languages: Vec<String>,
+
+    /// Add the input to the existing analysis of the ID instead of replacing it.
+    /// Input that was counted before is skipped
+    #[arg(short, long, default_value_t = false)]
+    append: bool,
+}
+
+fn parse_approximation(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("'{value}' is not of the form N=ENTRIES, such as 5=100000");
+    let (n, capacity) = value.split_once('=').ok_or_else(invalid)?;
+
+    Ok((
+        n.parse().map_err(|_| invalid())?,
+        capacity.parse().map_err(|_| invalid())?,
+    ))
+}
+
+fn parse_sample_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err(format!(
+            "'{value}' is not a fraction greater than 0 and at most 1"
+        )),
+    }
+}
+
+fn parse_similarity(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(similarity) if (0.0..=1.0).contains(&similarity) => Ok(similarity),
+        _ => Err(format!("'{value}' is not a similarity between 0 and 1")),
+    }
+}
+
+fn parse_shingle_len(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(len) if len > 0 => Ok(len),
+        _ => Err(format!("'{value}' is not a length of at least 1")),
+    }
 }
 
 #[derive(Debug, Subcommand)]
 enum AnalyseCommands {
     Wortschatz(AnalyseWortschatzArgs),
+    Markup(AnalyseMarkupArgs),
+    Epub(AnalyseEpubArgs),
+    Stdin(AnalyseStdinArgs),
+    Wet(AnalyseWetArgs),
+    Git(AnalyseGitArgs),
+    Keylog(AnalyseKeylogArgs),
 }
 
 #[derive(Debug, Args)]
 struct AnalyseWortschatzArgs {
     ids: Vec<String>,
+
+    /// Keep only sentences from this domain or its subdomains
+    #[arg(long = "domain", value_name = "DOMAIN")]
+    domains: Vec<String>,
+
+    /// Drop sentences from this domain or its subdomains
+    #[arg(long = "exclude-domain", value_name = "DOMAIN")]
+    excluded_domains: Vec<String>,
+
+    /// Keep only sentences published in this year
+    #[arg(long = "year", value_name = "YEAR")]
+    years: Vec<i32>,
+
+    /// Make an analysis for each source domain or publication year, stored as ID@DOMAIN or ID@YEAR
+    #[arg(long, value_enum)]
+    partition_by: Option<SourcePartition>,
+}
+
+/// Analyse the visible text of HTML and Markdown documents
+#[derive(Debug, Args)]
+struct AnalyseMarkupArgs {
+    /// ID to store the analysis under
+    #[arg(long)]
+    id: String,
+
+    /// Documents or directories of documents
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Format of the documents. Detected from file extensions if omitted
+    #[arg(long, value_enum)]
+    format: Option<MarkupFormat>,
+
+    /// Include code blocks and inline code from Markdown
+    #[arg(long, default_value_t = false)]
+    keep_code: bool,
+}
+
+/// Analyse EPUB books. Each book is stored under its file name
+#[derive(Debug, Args)]
+struct AnalyseEpubArgs {
+    /// Books or directories of books
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+/// Analyse text piped through standard input
+#[derive(Debug, Args)]
+struct AnalyseStdinArgs {
+    /// ID to store the analysis under
+    #[arg(long)]
+    id: String,
+}
+
+/// Analyse Common Crawl WET files with quality filtering
+#[derive(Debug, Args)]
+struct AnalyseWetArgs {
+    /// ID to store the analysis under
+    #[arg(long)]
+    id: String,
+
+    /// WET files (optionally gzipped) or directories of WET files
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Drop lines with fewer characters
+    #[arg(long, default_value_t = QualityFilter::default().min_line_length)]
+    min_line_length: usize,
+
+    /// Drop lines where a larger fraction of characters are not letters
+    #[arg(long, default_value_t = QualityFilter::default().max_symbol_ratio)]
+    max_symbol_ratio: f64,
+
+    /// Drop documents where a larger fraction of lines are repeated
+    #[arg(long, default_value_t = QualityFilter::default().max_line_repetition)]
+    max_line_repetition: f64,
+
+    /// Keep only documents identified by Common Crawl as primarily this language (ISO 639-3)
+    #[arg(long = "language", value_name = "LANGUAGE")]
+    languages: Vec<String>,
+}
+
+/// Analyse the commit messages of a git repository. Merge and bot commits are excluded
+#[derive(Debug, Args)]
+struct AnalyseGitArgs {
+    /// ID to store the analysis under
+    #[arg(long)]
+    id: String,
+
+    /// Path to the repository
+    repository: PathBuf,
+
+    /// Revision to read history from
+    #[arg(long, default_value = "HEAD")]
+    revision: String,
+
+    /// Also analyse lines added in the diffs of each commit
+    #[arg(long, default_value_t = false)]
+    include_diffs: bool,
+
+    /// Exclude commits by authors whose name or email contains this
+    #[arg(long = "exclude-author", value_name = "PATTERN")]
+    excluded_authors: Vec<String>,
+}
+
+/// Analyse keystroke logs. See `sources::keylog` for the format
+#[derive(Debug, Args)]
+struct AnalyseKeylogArgs {
+    /// ID to store the analysis under
+    #[arg(long)]
+    id: String,
+
+    /// Keystroke logs, or directories of keystroke logs with the extension `.keylog`
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Pauses longer than this many milliseconds separate bursts of typing
+    #[arg(long, default_value_t = 5000, value_name = "MS")]
+    max_pause: u64,
+}
+
+/// Sum analyses of shards of a corpus into one analysis
+#[derive(Debug, Args)]
+struct MergeArgs {
+    /// ID to store the merged analysis under
+    #[arg(long)]
+    id: String,
+
+    /// IDs of the analyses to merge. They must have been analysed with the same settings
+    #[arg(required = true)]
+    ids: Vec<String>,
+
+    #[arg(short, long, default_value_t = false, value_name = "?")]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct QueryArgs {
+    id: String,
+
+    /// Table to show, such as 'ngrams/2', 'skipgrams/1', 'words' or 'positions/initial'
+    #[arg(short, long, default_value = "ngrams/2")]
+    table: String,
+
+    /// Number of most frequent entries to show, if no entries are given
+    #[arg(long, default_value_t = 20, value_name = "N")]
+    top: usize,
+
+    /// Entries to show
+    entries: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -134,23 +476,160 @@ pub fn run() -> Result<()> {
             }
         },
 
-        Commands::Analyse(a_args) => match a_args.command {
-            AnalyseCommands::Wortschatz(a_ws_args) => {
-                for id in a_ws_args.ids {
-                    analyse::wortschatz(
-                        &id,
+        Commands::Analyse(a_args) => {
+            let a_args = *a_args;
+            let options = analyse::AnalyseOptions {
+                ngram_n: a_args.ngram_n,
+                skipgram_n: a_args.skipgram_n,
+                skipgram_shapes: a_args.skipgram_shapes,
+                approximate: a_args.approximate.into_iter().collect(),
+                pruning: a_args.prune_below.map(|min_count| Pruning {
+                    min_count,
+                    max_entries: a_args.prune_table_size,
+                }),
+                word_ngram_n: a_args.word_ngrams,
+                word_ngram_pruning: a_args.prune_word_ngrams_below.map(|min_count| Pruning {
+                    min_count,
+                    max_entries: a_args.prune_table_size,
+                }),
+                boundaries: Boundaries {
+                    sentence: a_args.sentence_boundaries,
+                    word: a_args.word_boundaries,
+                    span_sentences: a_args.span_sentences,
+                },
+                segmentation: a_args.segmentation,
+                normalization: a_args.normalization,
+                word_tokenizer: WordTokenizer {
+                    segmentation: a_args.word_segmentation,
+                    trim_punctuation: a_args.trim_punctuation,
+                    dictionary: match &a_args.dictionary {
+                        Some(path) => Some(Dictionary::from_path(path)?),
+                        None => None,
+                    },
+                },
+                show_progress: a_args.show_progress,
+                force: a_args.force,
+                append: a_args.append,
+                storage_format: args.storage_format,
+                language_filter: match &a_args.language {
+                    Some(language) => Some(analyse::language_filter(
+                        language,
+                        &a_args.other_languages,
                         work_dir,
-                        a_args.ngram_n,
-                        a_args.skipgram_n,
-                        a_args.show_progress,
-                        a_args.force,
-                    )?;
+                    )?),
+                    None => None,
+                },
+                bootstrap_shards: a_args.bootstrap_shards,
+                sampling: match (
+                    a_args.sample_first,
+                    a_args.sample_fraction,
+                    a_args.sample_reservoir,
+                ) {
+                    (Some(n), _, _) => Some(Sampling::First { n }),
+                    (_, Some(fraction), _) => Some(Sampling::Fraction {
+                        fraction,
+                        seed: a_args.seed,
+                    }),
+                    (_, _, Some(n)) => Some(Sampling::Reservoir {
+                        n,
+                        seed: a_args.seed,
+                    }),
+                    _ => None,
+                },
+                deduplication: (a_args.dedup || a_args.dedup_near.is_some()).then_some(
+                    Deduplication {
+                        near_threshold: a_args.dedup_near,
+                        shingle_len: a_args.shingle_len,
+                    },
+                ),
+            };
+
+            match a_args.command {
+                AnalyseCommands::Wortschatz(a_ws_args) => {
+                    let selection = SourceSelection {
+                        domains: a_ws_args.domains,
+                        excluded_domains: a_ws_args.excluded_domains,
+                        years: a_ws_args.years,
+                    };
+
+                    for id in a_ws_args.ids {
+                        analyse::wortschatz(
+                            &id,
+                            &selection,
+                            a_ws_args.partition_by,
+                            work_dir,
+                            &options,
+                        )?;
+                    }
+
+                    Ok(())
+                }
+                AnalyseCommands::Markup(a_mu_args) => analyse::markup(
+                    &a_mu_args.id,
+                    &a_mu_args.paths,
+                    a_mu_args.format,
+                    a_mu_args.keep_code,
+                    work_dir,
+                    &options,
+                ),
+                AnalyseCommands::Epub(a_ep_args) => {
+                    analyse::epub(&a_ep_args.paths, work_dir, &options)
                 }
+                AnalyseCommands::Stdin(a_in_args) => {
+                    analyse::stdin(&a_in_args.id, work_dir, &options)
+                }
+                AnalyseCommands::Wet(a_wet_args) => {
+                    let filter = QualityFilter {
+                        min_line_length: a_wet_args.min_line_length,
+                        max_symbol_ratio: a_wet_args.max_symbol_ratio,
+                        max_line_repetition: a_wet_args.max_line_repetition,
+                        languages: a_wet_args.languages,
+                    };
 
-                return Ok(());
+                    analyse::wet(
+                        &a_wet_args.id,
+                        &a_wet_args.paths,
+                        &filter,
+                        work_dir,
+                        &options,
+                    )
+                }
+                AnalyseCommands::Git(a_git_args) => analyse::git(
+                    &a_git_args.id,
+                    &a_git_args.repository,
+                    &a_git_args.revision,
+                    a_git_args.include_diffs,
+                    &a_git_args.excluded_authors,
+                    work_dir,
+                    &options,
+                ),
+                AnalyseCommands::Keylog(a_kl_args) => analyse::keylog(
+                    &a_kl_args.id,
+                    &a_kl_args.paths,
+                    a_kl_args.max_pause,
+                    work_dir,
+                    &options,
+                ),
             }
-        },
-        Commands::Report { id } => report::report(&id, work_dir),
+        }
+        Commands::Merge(m_args) => merge::merge(
+            &m_args.id,
+            &m_args.ids,
+            work_dir,
+            m_args.force,
+            args.storage_format,
+        ),
+        Commands::Report { id } => report::report(&id, work_dir, args.storage_format),
+        Commands::Query(q_args) => query::query(
+            &q_args.id,
+            &q_args.table,
+            &q_args.entries,
+            q_args.top,
+            work_dir,
+        ),
+        Commands::Convert(c_args) => {
+            convert::convert(c_args.kind, &c_args.ids, work_dir, args.storage_format)
+        }
         Commands::Export(e_args) => match e_args.command {
             ExportCommands::Oxeylyzer(oxey_args) => {
                 export::export_oxeylyzer(&oxey_args.id, work_dir, e_args.force)?;
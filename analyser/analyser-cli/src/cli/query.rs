@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use eyre::{eyre, Result, WrapErr};
+use indexmap::IndexMap;
+
+use crate::{
+    objects::report::Report,
+    occurance::{Countable, Occurances},
+};
+
+/// Prints the frequencies of entries of a table of a report, along with their confidence
+/// intervals if the report has them. Prints the `top` most frequent entries if none are given.
+pub fn query(
+    id: &str,
+    table: &str,
+    entries: &[String],
+    top: usize,
+    working_directory: &Path,
+) -> Result<()> {
+    let report = Report::from_id(id, working_directory)
+        .wrap_err_with(|| format!("Error reading report for ID '{id}'"))?;
+
+    let tables = report.analysis_frequencies.tables();
+    let frequencies = tables.get(table).ok_or_else(|| {
+        eyre!(
+            "Report '{id}' has no table '{table}'. Its tables are: {}",
+            tables.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })?;
+    let intervals = report
+        .frequency_intervals
+        .as_ref()
+        .map(|intervals| (intervals.lower.tables(), intervals.upper.tables()));
+
+    let keys: Vec<Countable> = if entries.is_empty() {
+        frequencies.keys().take(top).cloned().collect()
+    } else {
+        entries.iter().map(|entry| entry.as_str().into()).collect()
+    };
+
+    for key in keys {
+        let value = frequency(&tables, table, &key);
+        let interval = intervals
+            .as_ref()
+            .map(|(lower, upper)| (frequency(lower, table, &key), frequency(upper, table, &key)));
+
+        match interval {
+            Some((lower, upper)) => println!(
+                "{key:?}\t{:.4}%\t[{:.4}%, {:.4}%]",
+                value * 100.0,
+                lower * 100.0,
+                upper * 100.0
+            ),
+            None => println!("{key:?}\t{:.4}%", value * 100.0),
+        }
+    }
+
+    Ok(())
+}
+
+/// Frequency of `key` in a table, which is 0 if it was not counted
+fn frequency(tables: &IndexMap<String, &Occurances<f64>>, table: &str, key: &Countable) -> f64 {
+    tables
+        .get(table)
+        .and_then(|occurances| occurances.get(key))
+        .copied()
+        .unwrap_or(0.0)
+}
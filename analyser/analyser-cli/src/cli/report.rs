@@ -1,35 +1,36 @@
 use std::fs::create_dir_all;
-use std::io::{BufWriter, Write};
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 use chrono::Utc;
-use eyre::Result;
 use eyre::WrapErr;
+use eyre::{eyre, Result};
 
+use super::analyse::get_analysis_path;
+use crate::keymap::Keymap;
 use crate::objects::analysis::Analysis;
 use crate::objects::report::{Report, ReportMetadata, ReportRecipe, ReportSourceType};
 use crate::occurance::OccuranceAnalysis;
 use crate::transforms::TransformSpecification;
-use crate::utils::read_json;
+use crate::utils::{find_object, read_json, write_object, StorageFormat};
 
 impl Report {
     pub fn from_path(path: &Path) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let report: Report = serde_json::from_reader(reader)?;
-
-        Ok(report)
+        read_json(path)
     }
 
     pub fn from_id(id: &str, working_directory: &Path) -> Result<Self> {
-        let mut report_path = working_directory.to_owned();
-        report_path.push("report");
-        report_path.push(format!("{id}.json"));
-
-        Self::from_path(&report_path)
+        Self::from_path(&get_report_path(id, working_directory))
     }
 }
 
+pub fn get_report_path(id: &str, working_directory: &Path) -> PathBuf {
+    find_object(&working_directory.join("report"), id)
+}
+
 pub fn read_recipe(path: &Path) -> Result<ReportRecipe> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
@@ -38,14 +39,12 @@ pub fn read_recipe(path: &Path) -> Result<ReportRecipe> {
     Ok(recipe)
 }
 
-pub fn report(id: &str, working_directory: &Path) -> Result<()> {
+pub fn report(id: &str, working_directory: &Path, storage_format: StorageFormat) -> Result<()> {
     let mut recipe_path = working_directory.to_owned();
     recipe_path.push("recipe");
     recipe_path.push(format!("{id}.json"));
 
-    let mut output_path = working_directory.to_owned();
-    output_path.push("report");
-    output_path.push(format!("{id}.json"));
+    let output_path = get_report_path(id, working_directory);
     let _ = create_dir_all(&output_path.parent().unwrap());
 
     let recipe = read_recipe(&recipe_path)?;
@@ -53,6 +52,25 @@ pub fn report(id: &str, working_directory: &Path) -> Result<()> {
     let total_weight: f64 = recipe.sources.iter().map(|x| x.weight).sum();
     let mut analysis_counts = OccuranceAnalysis::<usize>::default();
     let mut analysis_weighted_counts = OccuranceAnalysis::<f64>::default();
+    let mut bootstrap_sources = Vec::new();
+
+    if let Some(pruning) = &recipe.pruning {
+        if pruning
+            .coverage
+            .is_some_and(|coverage| !(0.0..=1.0).contains(&coverage))
+        {
+            return Err(eyre!("Coverage of pruning must be between 0 and 1"));
+        }
+    }
+
+    if let Some(bootstrap) = &recipe.bootstrap {
+        if !(0.0..1.0).contains(&bootstrap.confidence) {
+            return Err(eyre!(
+                "Confidence of bootstrap intervals must be between 0 and 1, not {}",
+                bootstrap.confidence
+            ));
+        }
+    }
 
     for source in &recipe.sources {
         let id = &source.id;
@@ -61,35 +79,49 @@ pub fn report(id: &str, working_directory: &Path) -> Result<()> {
             strip_punctuation: source.strip_punctuation,
             strip_numbers: source.strip_numbers,
             strip_nonlatin: source.strip_nonlatin,
+            keystrokes: match &source.keymap {
+                Some(keymap_id) => Some(
+                    Keymap::from_id(keymap_id, working_directory)
+                        .wrap_err_with(|| format!("Error reading keymap '{}'", keymap_id))?
+                        .keystrokes(),
+                ),
+                None => None,
+            },
         };
 
-        let analysis: OccuranceAnalysis<usize> = match source.type_ {
+        let (analysis, mut shards) = match source.type_ {
             ReportSourceType::Analysis => {
-                let mut analysis_path = working_directory.to_owned();
-                analysis_path.push("analysis");
-                analysis_path.push(format!("{}.json", &id));
-
+                let analysis_path = get_analysis_path(id, working_directory);
                 let mut analysis: Analysis = read_json(&analysis_path)
                     .wrap_err_with(|| format!("Error reading analysis for ID '{}'. Maybe you didn't fetch and analyse this yet?", id))
                     ?;
 
-                analysis.analysis.transform(&trans_spec);
+                analysis.analysis.transform(&trans_spec)?;
                 analysis.analysis.sort();
-                analysis.analysis
+                (analysis.analysis, analysis.shards)
             }
             ReportSourceType::Report => {
-                let mut report_path = working_directory.to_owned();
-                report_path.push("report");
-                report_path.push(format!("{id}.json"));
-
+                let report_path = get_report_path(id, working_directory);
                 let report: Report = read_json(&report_path).wrap_err_with(|| format!("Error reading report for ID '{}'. Maybe you didn't generate the report for this this yet?", id))?;
                 let mut analysis = report.analysis_counts;
 
-                analysis.transform(&trans_spec);
+                analysis.transform(&trans_spec)?;
                 analysis.sort();
-                analysis
+                (analysis, Vec::new())
             }
         };
+
+        if recipe.bootstrap.is_some() {
+            if shards.is_empty() {
+                return Err(eyre!("Cannot bootstrap intervals from '{id}', as it has no shards. Analyse it with `--bootstrap-shards`"));
+            }
+
+            shards
+                .iter_mut()
+                .try_for_each(|shard| shard.transform(&trans_spec))?;
+            bootstrap_sources.push((shards, source.weight / total_weight));
+        }
+
         // Calculate ngram frequencies
         analysis_counts += analysis.clone();
         analysis_weighted_counts += analysis * (source.weight / total_weight);
@@ -102,6 +134,15 @@ pub fn report(id: &str, working_directory: &Path) -> Result<()> {
     // Restore counts as a usize
     let mut analysis_weighted_counts: OccuranceAnalysis<usize> = analysis_weighted_counts.into();
 
+    // Prune rare entries, before estimating intervals of the remaining frequencies
+    let pruned = match &recipe.pruning {
+        Some(pruning) => pruning.prune(
+            &mut analysis_weighted_counts,
+            &mut analysis_weighted_frequencies,
+        ),
+        None => Default::default(),
+    };
+
     // Sort
     analysis_counts.sort();
     analysis_weighted_counts.sort();
@@ -116,20 +157,32 @@ pub fn report(id: &str, working_directory: &Path) -> Result<()> {
         extra: recipe.metadata.extra.clone(),
         process_date: Utc::now(),
     };
+    let lengths = analysis_weighted_frequencies.lengths.summary();
+    let frequency_intervals = recipe
+        .bootstrap
+        .as_ref()
+        .map(|bootstrap| bootstrap.intervals(&bootstrap_sources, &analysis_weighted_frequencies));
+
     let report = Report {
         metadata: metadata,
         sources: recipe.sources,
         count: 0,
         analysis_counts: analysis_weighted_counts,
         analysis_frequencies: analysis_weighted_frequencies,
+        frequency_intervals,
+        pruned,
+        lengths,
     };
 
-    let report_file = File::create(&output_path)?;
+    let output_path = write_object(&output_path, &report, storage_format)?;
 
-    let mut report_file_buf = BufWriter::new(report_file);
-
-    serde_json::to_writer_pretty(&mut report_file_buf, &report)?;
-    report_file_buf.flush()?;
+    for (table, pruned_mass) in &report.pruned {
+        println!(
+            "Pruned {} entries of '{table}', {:.2}% of its frequency",
+            pruned_mass.entries,
+            pruned_mass.frequency * 100.0
+        );
+    }
 
     println!(
         "Finished making report. Report stored in {}",
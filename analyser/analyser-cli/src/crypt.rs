@@ -7,8 +7,15 @@ pub trait DigestExt {
     fn to_str(&self) -> String;
 }
 
-pub fn digest_sha256<R: Read>(mut reader: R) -> Result<Digest> {
+pub fn digest_sha256<R: Read>(reader: R) -> Result<Digest> {
     let mut context = Context::new(&SHA256);
+    update_sha256(&mut context, reader)?;
+
+    Ok(context.finish())
+}
+
+/// Adds everything read from `reader` to a SHA256 computation
+pub fn update_sha256<R: Read>(context: &mut Context, mut reader: R) -> Result<()> {
     let mut buffer = [0; 1024];
 
     loop {
@@ -20,5 +27,33 @@ pub fn digest_sha256<R: Read>(mut reader: R) -> Result<Digest> {
         context.update(&buffer[..count]);
     }
 
-    Ok(context.finish())
+    Ok(())
+}
+
+/// Reader that computes the SHA256 of everything read through it
+pub struct Sha256Reader<R: Read> {
+    inner: R,
+    context: Context,
+}
+
+impl<R: Read> Sha256Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            context: Context::new(&SHA256),
+        }
+    }
+
+    pub fn finish(self) -> Digest {
+        self.context.finish()
+    }
+}
+
+impl<R: Read> Read for Sha256Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.context.update(&buf[..count]);
+
+        Ok(count)
+    }
 }
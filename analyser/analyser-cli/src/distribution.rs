@@ -0,0 +1,205 @@
+use std::ops::AddAssign;
+
+use indexmap::IndexMap;
+use num_traits::NumCast;
+use serde::{Deserialize, Serialize};
+
+use crate::occurance::OccuranceT;
+
+/// Percentiles given in summaries of distributions
+pub const PERCENTILES: [u8; 6] = [10, 25, 50, 75, 90, 99];
+
+/// Histogram of occurances indexed by length
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Distribution<T: OccuranceT>(IndexMap<usize, T>);
+
+impl<T: OccuranceT> Distribution<T> {
+    /// Adds `value` to the occurances of `length`
+    pub fn increment(&mut self, length: usize, value: T) {
+        self.0
+            .entry(length)
+            .and_modify(|e| *e += value)
+            .or_insert(value);
+    }
+
+    /// Sorts by length, shortest first
+    pub fn sort(&mut self) {
+        self.0.sort_keys();
+    }
+
+    /// Applies `f` to the occurances of each length
+    pub fn map<U: OccuranceT>(self, f: impl Fn(T) -> U) -> Distribution<U> {
+        Distribution(
+            self.0
+                .into_iter()
+                .map(|(length, value)| (length, f(value)))
+                .collect(),
+        )
+    }
+}
+
+impl<T: OccuranceT + NumCast> Distribution<T> {
+    /// Mean, median and percentiles of the lengths, weighted by their occurances
+    pub fn summary(&self) -> DistributionSummary {
+        let mut lengths: Vec<(usize, f64)> = self
+            .0
+            .iter()
+            .map(|(&length, &value)| (length, NumCast::from(value).unwrap_or(0.0)))
+            .collect();
+        lengths.sort_by_key(|&(length, _)| length);
+
+        let total: f64 = lengths.iter().map(|(_, value)| value).sum();
+        if total <= 0.0 {
+            return DistributionSummary::default();
+        }
+
+        let mean = lengths
+            .iter()
+            .map(|&(length, value)| length as f64 * value)
+            .sum::<f64>()
+            / total;
+
+        // Smallest length with at least `percentile` % of the occurances at or below it
+        let percentile = |percentile: u8| -> usize {
+            let threshold = total * percentile as f64 / 100.0;
+            let mut cumulative = 0.0;
+
+            lengths
+                .iter()
+                .find(|&&(_, value)| {
+                    cumulative += value;
+                    cumulative >= threshold
+                })
+                .or(lengths.last())
+                .map_or(0, |&(length, _)| length)
+        };
+
+        DistributionSummary {
+            mean,
+            median: percentile(50),
+            percentiles: PERCENTILES.iter().map(|&p| (p, percentile(p))).collect(),
+        }
+    }
+}
+
+impl Distribution<f64> {
+    pub fn normalize(&mut self) {
+        let total: f64 = self.0.values().sum();
+        self.0.iter_mut().for_each(|(_, count)| *count /= total);
+    }
+}
+
+impl<T: OccuranceT> AddAssign for Distribution<T> {
+    fn add_assign(&mut self, other: Self) {
+        for (length, value) in other.0 {
+            self.increment(length, value);
+        }
+    }
+}
+
+/// Distributions of the lengths of words and sentences
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct LengthDistributions<T: OccuranceT> {
+    /// Length of words in units of the segmentation of the analysis.
+    /// Analyses made before segmentation was recorded counted graphemes
+    #[serde(alias = "word_graphemes")]
+    pub word_units: Distribution<T>,
+    /// Length of sentences in words, that is the number of words per sentence
+    pub sentence_words: Distribution<T>,
+    /// Length of sentences in units of the segmentation of the analysis, including whitespace
+    #[serde(alias = "sentence_graphemes")]
+    pub sentence_units: Distribution<T>,
+}
+
+impl<T: OccuranceT> LengthDistributions<T> {
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Distribution<T>> {
+        [
+            &mut self.word_units,
+            &mut self.sentence_words,
+            &mut self.sentence_units,
+        ]
+        .into_iter()
+    }
+
+    /// Applies `f` to the occurances of each length in each distribution
+    pub fn map<U: OccuranceT>(self, f: impl Fn(T) -> U) -> LengthDistributions<U> {
+        LengthDistributions {
+            word_units: self.word_units.map(&f),
+            sentence_words: self.sentence_words.map(&f),
+            sentence_units: self.sentence_units.map(&f),
+        }
+    }
+}
+
+impl<T: OccuranceT + NumCast> LengthDistributions<T> {
+    pub fn summary(&self) -> LengthSummaries {
+        LengthSummaries {
+            word_units: self.word_units.summary(),
+            sentence_words: self.sentence_words.summary(),
+            sentence_units: self.sentence_units.summary(),
+        }
+    }
+}
+
+impl<T: OccuranceT> AddAssign for LengthDistributions<T> {
+    fn add_assign(&mut self, other: Self) {
+        self.word_units += other.word_units;
+        self.sentence_words += other.sentence_words;
+        self.sentence_units += other.sentence_units;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct DistributionSummary {
+    pub mean: f64,
+    pub median: usize,
+    /// Length at each of `PERCENTILES`
+    pub percentiles: IndexMap<u8, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct LengthSummaries {
+    #[serde(alias = "word_graphemes")]
+    pub word_units: DistributionSummary,
+    pub sentence_words: DistributionSummary,
+    #[serde(alias = "sentence_graphemes")]
+    pub sentence_units: DistributionSummary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut distribution = Distribution::default();
+        distribution.increment(5, 1);
+        distribution.increment(2, 2);
+        distribution.increment(3, 1);
+
+        let mut other = Distribution::default();
+        other.increment(3, 1);
+        distribution += other;
+
+        let summary = distribution.summary();
+
+        assert_eq!(summary.mean, 3.0);
+        assert_eq!(summary.median, 3);
+        assert_eq!(summary.percentiles[&10], 2);
+        assert_eq!(summary.percentiles[&99], 5);
+        assert_eq!(Distribution::<usize>::default().summary().median, 0);
+    }
+
+    #[test]
+    fn test_grapheme_lengths_are_read_as_units() {
+        let lengths: LengthDistributions<usize> = serde_json::from_value(serde_json::json!({
+            "word_graphemes": { "2": 3 },
+            "sentence_words": {},
+            "sentence_graphemes": { "7": 1 },
+        }))
+        .unwrap();
+
+        assert_eq!(lengths.word_units.summary().median, 2);
+        assert_eq!(lengths.sentence_units.summary().median, 7);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hasher,
+    ops::AddAssign,
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
+
+/// Number of hash functions in the MinHash signature of each sentence
+const SIGNATURE_LEN: usize = 64;
+/// Signatures are split into bands of this many hashes. Sentences sharing a band are compared
+const BAND_LEN: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Deduplication {
+    /// Sentences with at least this estimated Jaccard similarity to an earlier sentence are
+    /// dropped as near-duplicates. Only exact duplicates are dropped if not given.
+    pub near_threshold: Option<f64>,
+
+    /// Length of the character shingles compared to find near-duplicates
+    pub shingle_len: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DeduplicationStats {
+    /// Number of sentences before de-duplication
+    pub sentences: usize,
+    pub exact_duplicates: usize,
+    pub near_duplicates: usize,
+}
+
+impl AddAssign for DeduplicationStats {
+    fn add_assign(&mut self, other: Self) {
+        self.sentences += other.sentences;
+        self.exact_duplicates += other.exact_duplicates;
+        self.near_duplicates += other.near_duplicates;
+    }
+}
+
+impl Deduplication {
+    /// Drops sentences that repeat an earlier sentence, keeping the first occurance
+    pub fn deduplicate(&self, sentences: Vec<String>) -> (Vec<String>, DeduplicationStats) {
+        let mut stats = DeduplicationStats {
+            sentences: sentences.len(),
+            ..Default::default()
+        };
+
+        let mut seen: HashSet<u64> = HashSet::new();
+        let sentences: Vec<String> = sentences
+            .into_iter()
+            .filter(|sentence| seen.insert(hash(normalize(sentence).as_bytes())))
+            .collect();
+        stats.exact_duplicates = stats.sentences - sentences.len();
+
+        let Some(threshold) = self.near_threshold else {
+            return (sentences, stats);
+        };
+
+        let signatures: Vec<[u64; SIGNATURE_LEN]> = sentences
+            .par_iter()
+            .map(|sentence| signature(sentence, self.shingle_len))
+            .collect();
+
+        // Locality-sensitive hashing: only sentences sharing a band of their signature are compared
+        let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+        let mut kept: Vec<bool> = vec![true; sentences.len()];
+
+        for (i, signature) in signatures.iter().enumerate() {
+            let bands: Vec<(usize, u64)> = signature
+                .chunks(BAND_LEN)
+                .enumerate()
+                .map(|(band, hashes)| {
+                    let bytes: Vec<u8> = hashes.iter().flat_map(|h| h.to_le_bytes()).collect();
+                    (band, hash(&bytes))
+                })
+                .collect();
+
+            let is_near_duplicate = bands
+                .iter()
+                .filter_map(|band| buckets.get(band))
+                .flatten()
+                .any(|&j| similarity(signature, &signatures[j]) >= threshold);
+
+            if is_near_duplicate {
+                kept[i] = false;
+                continue;
+            }
+
+            for band in bands {
+                buckets.entry(band).or_default().push(i);
+            }
+        }
+
+        let sentences: Vec<String> = sentences
+            .into_iter()
+            .zip(kept)
+            .filter_map(|(sentence, keep)| keep.then_some(sentence))
+            .collect();
+        stats.near_duplicates = stats.sentences - stats.exact_duplicates - sentences.len();
+
+        (sentences, stats)
+    }
+}
+
+fn normalize(sentence: &str) -> String {
+    sentence.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Hashes bytes with SipHash under fixed keys, such that de-duplication gives the same result
+/// on every platform and Rust release
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = SipHasher13::new_with_keys(0, 0);
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Mixes the bits of `x`, deriving the hash functions of the signature from a single hash
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// MinHash signature of the character shingles of a sentence
+fn signature(sentence: &str, shingle_len: usize) -> [u64; SIGNATURE_LEN] {
+    let sentence = normalize(sentence);
+    // Byte offsets of the characters, along with the end of the sentence
+    let offsets: Vec<usize> = sentence
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([sentence.len()])
+        .collect();
+    let shingles = offsets.windows(shingle_len.clamp(1, (offsets.len() - 1).max(1)) + 1);
+
+    let mut signature = [u64::MAX; SIGNATURE_LEN];
+    for shingle in shingles {
+        let shingle_hash = hash(&sentence.as_bytes()[shingle[0]..shingle[shingle.len() - 1]]);
+
+        for (i, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(splitmix64(shingle_hash ^ (i as u64)));
+        }
+    }
+
+    signature
+}
+
+/// Estimated Jaccard similarity of the shingles behind two signatures
+fn similarity(a: &[u64; SIGNATURE_LEN], b: &[u64; SIGNATURE_LEN]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    equal as f64 / SIGNATURE_LEN as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deduplicate() {
+        let sentences: Vec<String> = [
+            "cookies help us deliver our services to you.",
+            "cookies  help us deliver our services to you.",
+            "cookies help us deliver our services to you!",
+            "the weather is nice today.",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let exact = Deduplication {
+            near_threshold: None,
+            shingle_len: 5,
+        };
+        let (kept, stats) = exact.deduplicate(sentences.clone());
+        assert_eq!(kept.len(), 3);
+        assert_eq!(stats.exact_duplicates, 1);
+
+        let near = Deduplication {
+            near_threshold: Some(0.7),
+            ..exact
+        };
+        let (kept, stats) = near.deduplicate(sentences);
+        assert_eq!(
+            kept,
+            vec![
+                "cookies help us deliver our services to you.".to_owned(),
+                "the weather is nice today.".to_owned(),
+            ]
+        );
+        assert_eq!(stats.near_duplicates, 1);
+    }
+}
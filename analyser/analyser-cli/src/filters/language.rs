@@ -0,0 +1,188 @@
+use std::{collections::HashMap, ops::AddAssign};
+
+use indexmap::IndexMap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::occurance::{Countable, Occurances};
+
+/// Order of the character ngrams languages are told apart by
+pub const MODEL_N: usize = 3;
+
+/// Parameters of language identification, stored with the analysis
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LanguageIdentification {
+    /// Name of the model of the language to keep
+    pub language: String,
+    /// Names of all models sentences are compared against, including `language`
+    pub models: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LanguageStats {
+    /// Number of sentences before filtering
+    pub sentences: usize,
+    pub rejected: usize,
+    pub rejection_rate: f64,
+    /// Number of rejected sentences identified as each other language
+    pub rejected_by_language: IndexMap<String, usize>,
+}
+
+impl AddAssign for LanguageStats {
+    fn add_assign(&mut self, other: Self) {
+        self.sentences += other.sentences;
+        self.rejected += other.rejected;
+        self.rejection_rate = self.rejected as f64 / self.sentences.max(1) as f64;
+
+        for (language, rejected) in other.rejected_by_language {
+            *self.rejected_by_language.entry(language).or_default() += rejected;
+        }
+    }
+}
+
+/// Character ngram model of a language, such as one built from the counts of a report
+#[derive(Debug)]
+pub struct LanguageModel {
+    pub name: String,
+    log_probabilities: HashMap<Countable, f64>,
+    /// Log probability of ngrams that were never seen
+    unseen: f64,
+}
+
+impl LanguageModel {
+    /// Builds a model from counts of ngrams of order `MODEL_N`, with add-one smoothing
+    pub fn from_occurances(name: &str, ngrams: &Occurances<usize>) -> Self {
+        let total = (ngrams.sum() + ngrams.len() + 1) as f64;
+
+        Self {
+            name: name.to_owned(),
+            log_probabilities: ngrams
+                .iter()
+                .map(|(ngram, &count)| (ngram.clone(), ((count + 1) as f64 / total).ln()))
+                .collect(),
+            unseen: (1.0 / total).ln(),
+        }
+    }
+
+    /// Log likelihood of the ngrams of a sentence under the model
+    fn score(&self, ngrams: &[Countable]) -> f64 {
+        ngrams
+            .iter()
+            .map(|ngram| *self.log_probabilities.get(ngram).unwrap_or(&self.unseen))
+            .sum()
+    }
+}
+
+/// Keeps only the sentences most likely to be of the target language
+#[derive(Debug)]
+pub struct LanguageFilter {
+    /// Index in `models` of the language to keep
+    pub target: usize,
+    pub models: Vec<LanguageModel>,
+}
+
+impl LanguageFilter {
+    pub fn identification(&self) -> LanguageIdentification {
+        LanguageIdentification {
+            language: self.models[self.target].name.clone(),
+            models: self.models.iter().map(|model| model.name.clone()).collect(),
+        }
+    }
+
+    /// Gets the index of the most likely language of a sentence.
+    /// Sentences too short to hold an ngram are not identified.
+    pub fn identify(&self, sentence: &str) -> Option<usize> {
+        let padded = format!(" {sentence} ");
+        let graphemes: Vec<&str> = padded.graphemes(true).collect();
+        let ngrams: Vec<Countable> = graphemes
+            .windows(MODEL_N)
+            .map(|window| window.concat().into())
+            .collect();
+
+        if ngrams.is_empty() {
+            return None;
+        }
+
+        self.models
+            .iter()
+            .map(|model| model.score(&ngrams))
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    pub fn filter(&self, sentences: Vec<String>) -> (Vec<String>, LanguageStats) {
+        let identified: Vec<Option<usize>> = sentences
+            .par_iter()
+            .map(|sentence| self.identify(sentence))
+            .collect();
+
+        let mut stats = LanguageStats {
+            sentences: sentences.len(),
+            ..Default::default()
+        };
+
+        let kept = sentences
+            .into_iter()
+            .zip(identified)
+            .filter(|(_, language)| match language {
+                Some(language) if *language != self.target => {
+                    let name = self.models[*language].name.clone();
+                    *stats.rejected_by_language.entry(name).or_default() += 1;
+                    stats.rejected += 1;
+                    false
+                }
+                _ => true,
+            })
+            .map(|(sentence, _)| sentence)
+            .collect();
+        stats.rejection_rate = stats.rejected as f64 / stats.sentences.max(1) as f64;
+
+        (kept, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(name: &str, text: &str) -> LanguageModel {
+        let padded = format!(" {text} ");
+        let graphemes: Vec<&str> = padded.graphemes(true).collect();
+        let mut ngrams = Occurances::new();
+        for window in graphemes.windows(MODEL_N) {
+            ngrams.increment(window.concat().into(), 1);
+        }
+
+        LanguageModel::from_occurances(name, &ngrams)
+    }
+
+    #[test]
+    fn test_language_filter() {
+        let filter = LanguageFilter {
+            target: 0,
+            models: vec![
+                model(
+                    "danish",
+                    "hvad er det der sker med jer og hvordan går det i dag",
+                ),
+                model(
+                    "english",
+                    "what is happening with you and how are you doing today",
+                ),
+            ],
+        };
+
+        let sentences = vec![
+            "hvordan går det med jer".to_owned(),
+            "how are you doing".to_owned(),
+        ];
+        let (kept, stats) = filter.filter(sentences);
+
+        assert_eq!(kept, vec!["hvordan går det med jer"]);
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(stats.rejected_by_language["english"], 1);
+        assert_eq!(filter.identify(""), None);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod dedup;
+pub mod language;
+pub mod sample;
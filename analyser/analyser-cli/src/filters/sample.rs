@@ -0,0 +1,90 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Selects a subset of the sentences of a corpus. Random samples are drawn from a generator
+/// seeded with `seed`, such that the same input always gives the same sample.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Sampling {
+    /// The first `n` sentences
+    First { n: usize },
+    /// Each sentence is kept with probability `fraction`
+    Fraction { fraction: f64, seed: u64 },
+    /// Exactly `n` sentences drawn uniformly, or all of them if there are fewer
+    Reservoir { n: usize, seed: u64 },
+}
+
+impl Sampling {
+    /// Samples sentences, keeping them in their original order
+    pub fn sample(&self, sentences: Vec<String>) -> Vec<String> {
+        match *self {
+            Sampling::First { n } => sentences.into_iter().take(n).collect(),
+            Sampling::Fraction { fraction, seed } => {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                sentences
+                    .into_iter()
+                    .filter(|_| rng.gen::<f64>() < fraction)
+                    .collect()
+            }
+            Sampling::Reservoir { n, seed } => {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let mut reservoir: Vec<usize> = Vec::with_capacity(n.min(sentences.len()));
+
+                for i in 0..sentences.len() {
+                    if reservoir.len() < n {
+                        reservoir.push(i);
+                        continue;
+                    }
+
+                    let j = rng.gen_range(0..=i);
+                    if j < n {
+                        reservoir[j] = i;
+                    }
+                }
+                reservoir.sort_unstable();
+
+                let mut reservoir = reservoir.into_iter().peekable();
+                sentences
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| reservoir.next_if_eq(i).is_some())
+                    .map(|(_, sentence)| sentence)
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let sentences: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+
+        let first = Sampling::First { n: 3 }.sample(sentences.clone());
+        assert_eq!(first, vec!["0", "1", "2"]);
+
+        let fraction = Sampling::Fraction {
+            fraction: 0.1,
+            seed: 7,
+        };
+        let sample = fraction.sample(sentences.clone());
+        assert_eq!(sample, fraction.sample(sentences.clone()));
+        assert!(sample.len() > 50 && sample.len() < 150);
+
+        let reservoir = Sampling::Reservoir { n: 10, seed: 7 };
+        let sample = reservoir.sample(sentences.clone());
+        assert_eq!(sample, reservoir.sample(sentences.clone()));
+        assert_eq!(sample.len(), 10);
+        assert!(sample
+            .windows(2)
+            .all(|w| w[0].parse::<usize>().unwrap() < w[1].parse().unwrap()));
+        assert_ne!(
+            sample,
+            Sampling::Reservoir { n: 10, seed: 8 }.sample(sentences)
+        );
+    }
+}
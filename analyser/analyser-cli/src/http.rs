@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::{self, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
 };
 
@@ -35,7 +35,9 @@ pub fn download_file(url: Url, out_path: PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn download_and_decompress_archive(url: Url, out_path: &Path) -> Result<()> {
+/// Downloads a gzipped tar archive, storing the entries whose file names end with each suffix
+/// at the path paired with it. Fails if an entry is missing for any suffix.
+pub fn download_and_decompress_archive(url: Url, out_paths: &[(&str, &Path)]) -> Result<()> {
     let resp = get_response(url)?;
     let headers = resp.headers();
 
@@ -53,35 +55,34 @@ pub fn download_and_decompress_archive(url: Url, out_path: &Path) -> Result<()>
         "application/x-gzip" => {
             let decoded = GzDecoder::new(&content[..]);
             let mut archive = Archive::new(decoded);
+            let mut found = vec![false; out_paths.len()];
+
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let filename = match entry.path()?.file_name() {
+                    Some(filename) => filename.to_string_lossy().into_owned(),
+                    None => continue,
+                };
+
+                let Some(i) = out_paths
+                    .iter()
+                    .position(|(suffix, _)| filename.ends_with(suffix))
+                else {
+                    continue;
+                };
+                found[i] = true;
+
+                let file = File::create(out_paths[i].1)?;
+
+                let mut data_reader = BufReader::new(entry);
+                let mut data_writer = BufWriter::new(file);
+
+                io::copy(&mut data_reader, &mut data_writer)?;
+                data_writer.flush()?;
+            }
 
-            let sentences_entry = archive
-                .entries()?
-                .find_map(|e| {
-                    let entry = e.ok()?;
-                    let entry_path = entry.path().ok()?;
-                    let filename = entry_path.file_name()?;
-
-                    if filename.to_string_lossy().ends_with("-sentences.txt") {
-                        return Some(entry);
-                    }
-
-                    None
-                })
-                .ok_or_else(|| eyre!("Could not find sentences file!"))?;
-
-            let file = File::create(out_path)?;
-
-            let mut data_reader = BufReader::new(sentences_entry);
-            let mut data_writer = BufWriter::new(file);
-
-            let mut length = 1;
-            while length > 0 {
-                let buffer = data_reader.fill_buf()?;
-
-                let _ = data_writer.write(buffer);
-
-                length = buffer.len();
-                data_reader.consume(length);
+            if let Some(i) = found.iter().position(|found| !found) {
+                return Err(eyre!("Could not find '*{}' file!", out_paths[i].0));
             }
         }
 
@@ -1,9 +1,13 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
-use crate::crypt::digest_sha256;
+use crate::crypt::{digest_sha256, update_sha256};
 use chrono::{DateTime, Utc};
-use eyre::Result;
-use ring::digest::Digest;
+use eyre::{eyre, Result};
+use ring::digest::{Context, Digest, SHA256};
 use std::fs;
 
 pub fn file_sha256(path: &Path) -> Result<Digest> {
@@ -14,7 +18,58 @@ pub fn file_sha256(path: &Path) -> Result<Digest> {
     Ok(digest)
 }
 
+/// Computes the SHA256 of the concatenated contents of several files, reading one at a time
+pub fn files_sha256(paths: &[PathBuf]) -> Result<Digest> {
+    let mut context = Context::new(&SHA256);
+    for path in paths {
+        let input = File::open(path)?;
+        update_sha256(&mut context, BufReader::new(input))?;
+    }
+
+    Ok(context.finish())
+}
+
 pub fn get_mtime(path: &Path) -> Result<DateTime<Utc>> {
     let as_epoch = fs::metadata(path)?.modified()?;
     Ok(as_epoch.into())
 }
+
+/// Gets the most recent modification time among several files
+pub fn get_latest_mtime(paths: &[PathBuf]) -> Result<DateTime<Utc>> {
+    let mut latest: Option<DateTime<Utc>> = None;
+    for path in paths {
+        let mtime = get_mtime(path)?;
+        latest = latest.max(Some(mtime));
+    }
+
+    latest.ok_or_else(|| eyre!("No files given"))
+}
+
+/// Expands directories into the files they contain, recursively.
+/// Files within directories are only included if `include` returns true.
+/// The result is sorted such that it is stable across runs.
+pub fn collect_files(paths: &[PathBuf], include: &impl Fn(&Path) -> bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            let children: Vec<PathBuf> = fs::read_dir(path)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<_>>()?;
+
+            let children = collect_files(&children, include)?
+                .into_iter()
+                .filter(|child| include(child));
+            files.extend(children);
+        } else if path.exists() {
+            files.push(path.to_owned());
+        } else {
+            return Err(eyre!("Could not open '{}'", path.display()));
+        }
+    }
+
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
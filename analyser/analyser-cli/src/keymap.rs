@@ -0,0 +1,161 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use eyre::Result;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{occurance::Countable, sources::keylog::key_token};
+
+/// Modifiers held to reach each layer of a key
+const LAYER_MODIFIERS: [&[&str]; 4] = [&[], &["Shift"], &["AltGr"], &["Shift", "AltGr"]];
+
+/// A keyboard layout, describing which keys must be pressed to produce each character
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Keymap {
+    pub name: String,
+
+    /// Output of each key, by the output on its base layer.
+    /// Outputs are given for the base, Shift, AltGr and Shift+AltGr layers, in that order.
+    /// Outputs naming an entry in `dead_keys` are dead keys, empty outputs are unused.
+    pub keys: IndexMap<String, Vec<String>>,
+
+    /// Output of each dead key when followed by a character
+    #[serde(default)]
+    pub dead_keys: IndexMap<String, IndexMap<String, String>>,
+
+    /// Output of sequences of characters typed after the Compose key
+    #[serde(default)]
+    pub compose: IndexMap<String, String>,
+}
+
+impl Keymap {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let keymap: Keymap = serde_json::from_reader(reader)?;
+
+        Ok(keymap)
+    }
+
+    pub fn from_id(id: &str, working_directory: &Path) -> Result<Self> {
+        let mut keymap_path = working_directory.to_owned();
+        keymap_path.push("keymap");
+        keymap_path.push(format!("{id}.json"));
+
+        Self::from_path(&keymap_path)
+    }
+
+    /// Gets the shortest sequence of keystrokes producing each character of the keymap
+    pub fn keystrokes(&self) -> HashMap<Countable, Vec<Countable>> {
+        let mut keystrokes: HashMap<Countable, Vec<Countable>> = HashMap::new();
+        let mut dead_keystrokes: HashMap<&str, Vec<Countable>> = HashMap::new();
+
+        let insert = |map: &mut HashMap<Countable, Vec<Countable>>,
+                      output: &str,
+                      sequence: Vec<Countable>| {
+            if sequence.is_empty() {
+                return;
+            }
+
+            let is_shorter = map
+                .get(output)
+                .is_none_or(|existing| sequence.len() < existing.len());
+            if is_shorter {
+                map.insert(output.into(), sequence);
+            }
+        };
+
+        for (key, outputs) in &self.keys {
+            for (output, modifiers) in outputs.iter().zip(LAYER_MODIFIERS) {
+                if output.is_empty() {
+                    continue;
+                }
+
+                let sequence: Vec<Countable> = modifiers
+                    .iter()
+                    .map(|modifier| key_token(modifier))
+                    .chain([key_token(key)])
+                    .collect();
+
+                if self.dead_keys.contains_key(output) {
+                    dead_keystrokes.entry(output).or_insert(sequence);
+                } else {
+                    insert(&mut keystrokes, output, sequence);
+                }
+            }
+        }
+
+        for (dead_key, combinations) in &self.dead_keys {
+            let Some(dead_sequence) = dead_keystrokes.get(dead_key.as_str()) else {
+                continue;
+            };
+
+            for (base, output) in combinations {
+                if let Some(base_sequence) = keystrokes.get(base.as_str()).cloned() {
+                    insert(
+                        &mut keystrokes,
+                        output,
+                        [dead_sequence.clone(), base_sequence].concat(),
+                    );
+                }
+            }
+        }
+
+        for (sequence, output) in &self.compose {
+            let typed: Option<Vec<Vec<Countable>>> = sequence
+                .chars()
+                .map(|c| keystrokes.get(c.to_string().as_str()).cloned())
+                .collect();
+
+            if let Some(typed) = typed {
+                let compose_sequence = [vec![key_token("Compose")], typed.concat()].concat();
+                insert(&mut keystrokes, output, compose_sequence);
+            }
+        }
+
+        keystrokes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystrokes() {
+        let keymap: Keymap = serde_json::from_str(
+            r#"{
+                "name": "Test",
+                "keys": {
+                    "e": ["e", "E", "€"],
+                    "o": ["o", "O"],
+                    "´": ["dead_acute", "dead_grave", "|"],
+                    "x": ["x", "X", "", "é"]
+                },
+                "dead_keys": {
+                    "dead_acute": {"e": "é", "E": "É"},
+                    "dead_grave": {"e": "è"}
+                },
+                "compose": {"oe": "œ"}
+            }"#,
+        )
+        .unwrap();
+
+        let keystrokes = keymap.keystrokes();
+        let get = |c: &str| -> Vec<&str> {
+            keystrokes[&Countable::from(c)]
+                .iter()
+                .map(|k| k.as_str())
+                .collect()
+        };
+
+        assert_eq!(get("e"), vec!["e"]);
+        assert_eq!(get("E"), vec!["<Shift>", "e"]);
+        assert_eq!(get("€"), vec!["<AltGr>", "e"]);
+        assert_eq!(get("é"), vec!["´", "e"]);
+        assert_eq!(get("É"), vec!["´", "<Shift>", "e"]);
+        assert_eq!(get("è"), vec!["<Shift>", "´", "e"]);
+        assert_eq!(get("œ"), vec!["<Compose>", "o", "e"]);
+        assert!(!keystrokes.contains_key(&Countable::from("dead_acute")));
+    }
+}
@@ -3,14 +3,23 @@
 use eyre::Result;
 
 mod analyse;
+mod bootstrap;
 mod cli;
 mod crypt;
+mod distribution;
+mod filters;
 mod http;
 mod io;
+mod keymap;
 mod objects;
 mod occurance;
+mod pruning;
+mod segmentation;
+mod sketch;
+mod sources;
 mod transforms;
 mod utils;
+mod words;
 
 fn main() -> Result<()> {
     cli::run()
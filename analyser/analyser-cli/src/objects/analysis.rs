@@ -1,14 +1,25 @@
 use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::occurance::OccuranceAnalysis;
+use crate::{
+    analyse::AnalysisSpecification,
+    filters::{dedup::DeduplicationStats, language::LanguageStats},
+    occurance::OccuranceAnalysis,
+    sketch::Approximations,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Analysis {
     pub source: AnalysisSource,
     pub metadata: AnalysisMetadata,
     pub analysis: OccuranceAnalysis<usize>,
+
+    /// Counts of contiguous shards of the sentences, summing to `analysis`.
+    /// Only kept if the specification asks for bootstrap shards
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shards: Vec<OccuranceAnalysis<usize>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,9 +31,79 @@ pub struct AnalysisSource {
 
     pub date: DateTime<Utc>,
     pub hash: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// Revision of the source, such as a commit hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// Hashes of every input counted, for analyses built from several inputs.
+    /// `hash` is that of the latest input.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AnalysisMetadata {
     pub date: DateTime<Utc>,
+
+    /// Not known for analyses made before it was recorded
+    #[serde(default)]
+    pub specification: Option<AnalysisSpecification>,
+
+    /// Error bounds of the ngram orders that were counted approximately
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub approximations: Approximations,
+
+    /// Number of sentences dropped as duplicates, if de-duplicated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deduplication: Option<DeduplicationStats>,
+
+    /// Number of sentences rejected as other languages, if filtered by language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<LanguageStats>,
+}
+
+#[cfg(test)]
+impl Analysis {
+    /// Analysis of `num_sentences` sentences of an input with the given hash
+    pub fn for_test(hash: &str, num_sentences: usize) -> Self {
+        let specification = serde_json::from_value(serde_json::json!({
+            "ngram_ns": [1],
+            "skipgram_ns": [],
+        }))
+        .unwrap();
+
+        Self {
+            source: AnalysisSource {
+                origin_id: "test".to_owned(),
+                origin_name: "Test".to_owned(),
+                origin_url: Url::parse("file:///dev/null").unwrap(),
+                license: String::new(),
+                date: Utc::now(),
+                hash: hash.to_owned(),
+                title: None,
+                author: None,
+                revision: None,
+                hashes: Vec::new(),
+            },
+            metadata: AnalysisMetadata {
+                date: Utc::now(),
+                specification: Some(specification),
+                approximations: Approximations::new(),
+                deduplication: None,
+                language: None,
+            },
+            analysis: OccuranceAnalysis {
+                num_sentences,
+                ..Default::default()
+            },
+            shards: Vec::new(),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use eyre::Result;
 use indexmap::IndexMap;
 use serde::Serialize;
 
@@ -5,6 +6,9 @@ use crate::occurance::Countable;
 
 use super::report::Report;
 
+/// Lower and upper bounds of the frequencies of a table
+pub type FrequencyBounds = IndexMap<Countable, (f64, f64)>;
+
 #[derive(Serialize, Debug)]
 pub struct OxeylyserLanguageData {
     pub language: String,
@@ -18,18 +22,69 @@ pub struct OxeylyserLanguageData {
     pub skipgrams3: IndexMap<Countable, f64>,
 }
 
+/// Confidence intervals of the tables of `OxeylyserLanguageData`, by the same names. Kept apart
+/// from the language data, such that it stays in the format oxeylyzer reads.
+#[derive(Serialize, Debug)]
+pub struct OxeylyserIntervals {
+    pub language: String,
+
+    /// Lower and upper bounds of the frequencies of each table
+    pub bounds: IndexMap<String, FrequencyBounds>,
+}
+
 impl OxeylyserLanguageData {
-    pub fn from_report(report: &Report) -> Self {
-        Self {
+    pub fn from_report(report: &Report) -> Result<Self> {
+        let frequencies = &report.analysis_frequencies;
+
+        Ok(Self {
             language: report.metadata.id.clone(),
 
-            characters: report.analysis_frequencies.ngrams[&1].clone().into(),
-            bigrams: report.analysis_frequencies.ngrams[&2].clone().into(),
-            trigrams: report.analysis_frequencies.ngrams[&3].clone().into(),
+            characters: frequencies.ngram_table(1)?.clone().into(),
+            bigrams: frequencies.ngram_table(2)?.clone().into(),
+            trigrams: frequencies.ngram_table(3)?.clone().into(),
 
-            skipgrams: report.analysis_frequencies.skipgrams[&1].clone().into(),
-            skipgrams2: report.analysis_frequencies.skipgrams[&2].clone().into(),
-            skipgrams3: report.analysis_frequencies.skipgrams[&3].clone().into(),
-        }
+            skipgrams: frequencies.skipgram_table(1)?.clone().into(),
+            skipgrams2: frequencies.skipgram_table(2)?.clone().into(),
+            skipgrams3: frequencies.skipgram_table(3)?.clone().into(),
+        })
+    }
+}
+
+impl OxeylyserIntervals {
+    /// Gets the intervals of the exported tables, if the report has them
+    pub fn from_report(report: &Report) -> Option<Self> {
+        let intervals = report.frequency_intervals.as_ref()?;
+
+        let tables = [
+            ("characters", "ngrams/1"),
+            ("bigrams", "ngrams/2"),
+            ("trigrams", "ngrams/3"),
+            ("skipgrams", "skipgrams/1"),
+            ("skipgrams2", "skipgrams/2"),
+            ("skipgrams3", "skipgrams/3"),
+        ];
+        let lower = intervals.lower.tables();
+        let upper = intervals.upper.tables();
+
+        let bounds = tables
+            .into_iter()
+            .filter_map(|(name, table)| {
+                let lower = lower.get(table)?;
+                let upper = upper.get(table)?;
+                let bounds = lower
+                    .iter()
+                    .map(|(key, low)| {
+                        (key.clone(), (*low, upper.get(key).copied().unwrap_or(*low)))
+                    })
+                    .collect();
+
+                Some((name.to_owned(), bounds))
+            })
+            .collect();
+
+        Some(Self {
+            language: report.metadata.id.clone(),
+            bounds,
+        })
     }
 }
@@ -1,9 +1,13 @@
 use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::bootstrap::{Bootstrap, FrequencyIntervals};
+use crate::distribution::LengthSummaries;
 use crate::occurance::OccuranceAnalysis;
+use crate::pruning::{PrunedMass, ReportPruning};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Report {
@@ -13,6 +17,18 @@ pub struct Report {
     pub count: u64,
     pub analysis_counts: OccuranceAnalysis<usize>,
     pub analysis_frequencies: OccuranceAnalysis<f64>,
+
+    /// Confidence intervals of `analysis_frequencies`, if the recipe asks for them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_intervals: Option<FrequencyIntervals>,
+
+    /// Mass dropped from each table pruned by the recipe
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub pruned: IndexMap<String, PrunedMass>,
+
+    /// Summaries of the weighted length distributions
+    #[serde(default)]
+    pub lengths: LengthSummaries,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,6 +55,14 @@ pub struct ReportRecipeMetadata {
 pub struct ReportRecipe {
     pub metadata: ReportRecipeMetadata,
     pub sources: Vec<ReportSource>,
+
+    /// Bootstrap confidence intervals of the frequencies from the shards of the sources
+    #[serde(default)]
+    pub bootstrap: Option<Bootstrap>,
+
+    /// Pruning of rare entries from the tables of the report
+    #[serde(default)]
+    pub pruning: Option<ReportPruning>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -60,6 +84,10 @@ pub struct ReportSource {
 
     #[serde(default)]
     pub strip_nonlatin: bool,
+
+    /// ID of a keymap to re-express the source in keystrokes with
+    #[serde(default)]
+    pub keymap: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
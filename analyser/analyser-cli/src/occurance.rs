@@ -10,8 +10,11 @@ use num_traits::NumCast;
 use serde::{Deserialize, Serialize};
 
 use delegate::delegate;
+use eyre::{eyre, Result};
 use smartstring::{LazyCompact, SmartString};
 
+use crate::distribution::LengthDistributions;
+
 pub trait NGramLikeT = AddAssign + Send + Copy + Default;
 
 pub type Countable = SmartString<LazyCompact>;
@@ -33,12 +36,25 @@ impl<T: OccuranceT> Occurances<T> {
         self.0.values().cloned().sum()
     }
 
+    /// Adds `value` to the occurances of `key`
+    pub fn increment(&mut self, key: Countable, value: T) {
+        self.0
+            .entry(key)
+            .and_modify(|e| *e += value)
+            .or_insert(value);
+    }
+
     delegate! {
         to self.0 {
             pub fn into_iter(self) -> impl Iterator<Item = (Countable, T)>;
+            pub fn iter(&self) -> impl Iterator<Item = (&Countable, &T)>;
             pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Countable, &mut T)>;
             pub fn get(&self, key: &Countable) -> Option<&T>;
             pub fn keys(&self) -> Keys<Countable, T>;
+            pub fn len(&self) -> usize;
+            pub fn is_empty(&self) -> bool;
+            pub fn retain<F>(&mut self, keep: F)
+                where F: FnMut(&Countable, &mut T) -> bool;
             pub fn entry(&mut self, key: Countable) -> Entry<Countable, T>;
             pub fn par_sort_by<F>(&mut self, cmp: F)
                 where F: Fn(&Countable, &T, &Countable, &T) -> Ordering + Sync;
@@ -103,6 +119,10 @@ where
     }
 }
 
+/// A map of occurances indexed by the shape of skipgram they were counted with.
+/// Can be a_b, a__b, ab_c, etc
+pub type ShapedOccurances<T> = IndexMap<String, Occurances<T>>;
+
 pub type NOccurances<T> = IndexMap<usize, Occurances<T>>;
 /// A map of occurances indexed by the ordinate of the occurances.
 /// Can be ngram1, ngram2, skipgram1, skipgram2, etc
@@ -114,22 +134,195 @@ where
 {
     pub ngrams: NOccurances<T>,
     pub skipgrams: NOccurances<T>,
+    #[serde(default)]
+    pub shaped_skipgrams: ShapedOccurances<T>,
     pub words: Occurances<T>,
+    /// Sequences of consecutive words within a sentence, indexed by the number of words.
+    /// The words of each sequence are joined by spaces
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub word_ngrams: NOccurances<T>,
+    #[serde(default)]
+    pub positions: PositionalOccurances<T>,
+    #[serde(default)]
+    pub lengths: LengthDistributions<T>,
     pub num_sentences: usize,
 }
 
+/// Occurances of graphemes by their position within words
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PositionalOccurances<T>
+where
+    T: OccuranceT,
+{
+    /// First grapheme of each word
+    pub initial: Occurances<T>,
+    /// Graphemes that are neither first nor last in their word
+    pub medial: Occurances<T>,
+    /// Last grapheme of each word. Single-grapheme words are both initial and final
+    #[serde(rename = "final")]
+    pub final_: Occurances<T>,
+    /// Graphemes indexed by their position within the word, starting from 1
+    pub indexed: NOccurances<T>,
+}
+
+impl<T> PositionalOccurances<T>
+where
+    T: OccuranceT,
+{
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Occurances<T>> {
+        [&mut self.initial, &mut self.medial, &mut self.final_]
+            .into_iter()
+            .chain(self.indexed.values_mut())
+    }
+
+    /// Applies `f` to each of the tables
+    pub fn map<U: OccuranceT>(
+        self,
+        f: impl Fn(Occurances<T>) -> Occurances<U>,
+    ) -> PositionalOccurances<U> {
+        PositionalOccurances {
+            initial: f(self.initial),
+            medial: f(self.medial),
+            final_: f(self.final_),
+            indexed: self
+                .indexed
+                .into_iter()
+                .map(|(position, occurances)| (position, f(occurances)))
+                .collect(),
+        }
+    }
+}
+
+impl<T> AddAssign for PositionalOccurances<T>
+where
+    T: OccuranceT,
+{
+    fn add_assign(&mut self, other: Self) {
+        self.initial += other.initial;
+        self.medial += other.medial;
+        self.final_ += other.final_;
+
+        for (position, occurance) in other.indexed {
+            let entry = self.indexed.entry(position).or_default();
+            *entry += occurance;
+        }
+    }
+}
+
 impl<T> OccuranceAnalysis<T>
 where
     T: OccuranceT,
 {
     pub fn sort(&mut self) {
-        let occurances = self.ngrams.values_mut().chain(self.skipgrams.values_mut());
+        let occurances = self
+            .ngrams
+            .values_mut()
+            .chain(self.skipgrams.values_mut())
+            .chain(self.shaped_skipgrams.values_mut())
+            .chain(self.word_ngrams.values_mut())
+            .chain(self.positions.values_mut());
         occurances.for_each(|occurance| {
             occurance.par_sort_by(|_, v1, _, v2| v2.partial_cmp(v1).unwrap());
         });
 
         self.words
             .par_sort_by(|_, v1, _, v2| v2.partial_cmp(v1).unwrap());
+
+        self.lengths
+            .values_mut()
+            .for_each(|distribution| distribution.sort());
+    }
+}
+
+impl<T> OccuranceAnalysis<T>
+where
+    T: OccuranceT,
+{
+    /// Gets the ngrams of order `n`, failing if they were not counted
+    pub fn ngram_table(&self, n: usize) -> Result<&Occurances<T>> {
+        self.ngrams.get(&n).ok_or_else(|| {
+            eyre!(
+                "No ngrams of order {n} were counted. Analyse the sources with `-n {n}` or higher"
+            )
+        })
+    }
+
+    /// Gets every table of occurances by a name unique within the analysis,
+    /// such as `ngrams/2`, `shaped_skipgrams/a_b`, `words`, `word_ngrams/2` or `positions/initial`
+    pub fn tables(&self) -> IndexMap<String, &Occurances<T>> {
+        let mut tables = IndexMap::new();
+        tables.extend(self.ngrams.iter().map(|(n, o)| (format!("ngrams/{n}"), o)));
+        tables.extend(
+            self.skipgrams
+                .iter()
+                .map(|(n, o)| (format!("skipgrams/{n}"), o)),
+        );
+        tables.extend(
+            self.shaped_skipgrams
+                .iter()
+                .map(|(shape, o)| (format!("shaped_skipgrams/{shape}"), o)),
+        );
+        tables.insert("words".to_owned(), &self.words);
+        tables.extend(
+            self.word_ngrams
+                .iter()
+                .map(|(n, o)| (format!("word_ngrams/{n}"), o)),
+        );
+        tables.insert("positions/initial".to_owned(), &self.positions.initial);
+        tables.insert("positions/medial".to_owned(), &self.positions.medial);
+        tables.insert("positions/final".to_owned(), &self.positions.final_);
+        tables.extend(
+            self.positions
+                .indexed
+                .iter()
+                .map(|(i, o)| (format!("positions/{i}"), o)),
+        );
+
+        tables
+    }
+
+    /// Mutable counterpart of `tables`, naming the tables the same
+    pub fn tables_mut(&mut self) -> IndexMap<String, &mut Occurances<T>> {
+        let mut tables = IndexMap::new();
+        tables.extend(
+            self.ngrams
+                .iter_mut()
+                .map(|(n, o)| (format!("ngrams/{n}"), o)),
+        );
+        tables.extend(
+            self.skipgrams
+                .iter_mut()
+                .map(|(n, o)| (format!("skipgrams/{n}"), o)),
+        );
+        tables.extend(
+            self.shaped_skipgrams
+                .iter_mut()
+                .map(|(shape, o)| (format!("shaped_skipgrams/{shape}"), o)),
+        );
+        tables.insert("words".to_owned(), &mut self.words);
+        tables.extend(
+            self.word_ngrams
+                .iter_mut()
+                .map(|(n, o)| (format!("word_ngrams/{n}"), o)),
+        );
+        tables.insert("positions/initial".to_owned(), &mut self.positions.initial);
+        tables.insert("positions/medial".to_owned(), &mut self.positions.medial);
+        tables.insert("positions/final".to_owned(), &mut self.positions.final_);
+        tables.extend(
+            self.positions
+                .indexed
+                .iter_mut()
+                .map(|(i, o)| (format!("positions/{i}"), o)),
+        );
+
+        tables
+    }
+
+    /// Gets the skipgrams of order `n`, failing if they were not counted
+    pub fn skipgram_table(&self, n: usize) -> Result<&Occurances<T>> {
+        self.skipgrams.get(&n).ok_or_else(|| {
+            eyre!("No skipgrams of order {n} were counted. Analyse the sources with `-k {n}` or higher")
+        })
     }
 }
 
@@ -139,10 +332,24 @@ impl OccuranceAnalysis<f64> {
         self.ngrams
             .values_mut()
             .chain(self.skipgrams.values_mut())
+            .chain(self.shaped_skipgrams.values_mut())
             .for_each(|occurances| occurances.normalize());
 
-        // Normalise occurances of words
+        // Normalise occurances of words, and of word ngrams by their order
         self.words.normalize();
+        self.word_ngrams
+            .values_mut()
+            .for_each(|occurances| occurances.normalize());
+
+        // Normalise each positional table on its own
+        self.positions
+            .values_mut()
+            .for_each(|occurances| occurances.normalize());
+
+        // Normalise each length distribution on its own
+        self.lengths
+            .values_mut()
+            .for_each(|distribution| distribution.normalize());
     }
 }
 
@@ -161,7 +368,20 @@ where
             *entry += occurance;
         }
 
+        for (shape, occurance) in other.shaped_skipgrams {
+            let entry = self.shaped_skipgrams.entry(shape).or_default();
+            *entry += occurance;
+        }
+
         self.words += other.words;
+
+        for (n, occurance) in other.word_ngrams {
+            let entry = self.word_ngrams.entry(n).or_default();
+            *entry += occurance;
+        }
+
+        self.positions += other.positions;
+        self.lengths += other.lengths;
         self.num_sentences += other.num_sentences;
     }
 }
@@ -188,20 +408,53 @@ where
             (n, new_counter)
         });
 
+        let shaped_skipgrams = self.shaped_skipgrams.into_iter().map(|(shape, counter)| {
+            let new_counter = counter
+                .into_iter()
+                .map(|(s, count)| (s, NumCast::from(count).unwrap_or(0.0) * rhs))
+                .collect::<Occurances<f64>>();
+            (shape, new_counter)
+        });
+
         let words = self
             .words
             .into_iter()
             .map(|(s, count)| (s, NumCast::from(count).unwrap_or(0.0) * rhs));
 
+        let word_ngrams = self.word_ngrams.into_iter().map(|(n, counter)| {
+            let new_counter = counter
+                .into_iter()
+                .map(|(s, count)| (s, NumCast::from(count).unwrap_or(0.0) * rhs))
+                .collect::<Occurances<f64>>();
+            (n, new_counter)
+        });
+
+        let positions = self.positions.map(|counter| {
+            counter
+                .into_iter()
+                .map(|(s, count)| (s, NumCast::from(count).unwrap_or(0.0) * rhs))
+                .collect()
+        });
+
+        let lengths = self
+            .lengths
+            .map(|count| NumCast::from(count).unwrap_or(0.0) * rhs);
+
         let ngrams: NOccurances<f64> = ngrams.collect();
         let skipgrams: NOccurances<f64> = skipgrams.collect();
+        let shaped_skipgrams: ShapedOccurances<f64> = shaped_skipgrams.collect();
         let words: Occurances<f64> = words.collect();
+        let word_ngrams: NOccurances<f64> = word_ngrams.collect();
         let num_sentences = self.num_sentences;
 
         OccuranceAnalysis {
             ngrams,
             skipgrams,
+            shaped_skipgrams,
             words,
+            word_ngrams,
+            positions,
+            lengths,
             num_sentences,
         }
     }
@@ -221,13 +474,30 @@ impl From<OccuranceAnalysis<f64>> for OccuranceAnalysis<usize> {
             .map(|(n, counter)| (n, counter.into()))
             .collect();
 
+        let shaped_skipgrams = occurance_analysis
+            .shaped_skipgrams
+            .into_iter()
+            .map(|(shape, counter)| (shape, counter.into()))
+            .collect();
+
         let words = occurance_analysis.words.into();
+        let word_ngrams = occurance_analysis
+            .word_ngrams
+            .into_iter()
+            .map(|(n, counter)| (n, counter.into()))
+            .collect();
+        let positions = occurance_analysis.positions.map(|counter| counter.into());
+        let lengths = occurance_analysis.lengths.map(|count| count as usize);
         let num_sentences = occurance_analysis.num_sentences;
 
         OccuranceAnalysis {
             ngrams,
             skipgrams,
+            shaped_skipgrams,
             words,
+            word_ngrams,
+            positions,
+            lengths,
             num_sentences,
         }
     }
@@ -241,7 +511,7 @@ mod tests {
         let src: Vec<Countable> = vec!["aa".into(), "bb".into(), "aa".into()];
         let occurances: OccuranceCounter = src.into_iter().collect();
 
-        assert_eq!(occurances.get("aa".into()), Some(&2)); // "aa" occurs twice
-        assert_eq!(occurances.get("bb".into()), Some(&1)); // "bb" occurs once
+        assert_eq!(occurances.get(&Countable::from("aa")), Some(&2)); // "aa" occurs twice
+        assert_eq!(occurances.get(&Countable::from("bb")), Some(&1)); // "bb" occurs once
     }
 }
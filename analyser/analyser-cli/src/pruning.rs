@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::occurance::{Countable, OccuranceAnalysis};
+
+/// Pruning of the tables of a report, dropping rare entries once every source is added.
+/// Entries are kept only if they pass every given criterion.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ReportPruning {
+    /// Drop entries with a lower weighted count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_count: Option<usize>,
+
+    /// Drop entries with a lower frequency within their table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_frequency: Option<f64>,
+
+    /// Keep only this many of the most frequent entries of each table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+
+    /// Keep only the fewest most frequent entries of each table that make up this share of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<f64>,
+}
+
+/// What was dropped from a table by pruning. Frequencies are not renormalised afterwards, such
+/// that `frequency` is what the remaining frequencies of the table fall short of summing to.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PrunedMass {
+    pub entries: usize,
+    pub count: usize,
+    pub frequency: f64,
+}
+
+impl ReportPruning {
+    /// Prunes the same entries from the counts and frequencies of a report, which must share
+    /// their tables and keys. Returns the mass dropped from each table that was pruned.
+    pub fn prune(
+        &self,
+        counts: &mut OccuranceAnalysis<usize>,
+        frequencies: &mut OccuranceAnalysis<f64>,
+    ) -> IndexMap<String, PrunedMass> {
+        let mut pruned_masses = IndexMap::new();
+        let mut count_tables = counts.tables_mut();
+
+        for (name, frequency_table) in frequencies.tables_mut() {
+            let Some(count_table) = count_tables.get_mut(&name) else {
+                continue;
+            };
+
+            let mut entries: Vec<(&Countable, f64)> = frequency_table
+                .iter()
+                .map(|(key, frequency)| (key, *frequency))
+                .collect();
+            entries.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+            let total: f64 = entries.iter().map(|(_, frequency)| frequency).sum();
+            let mut covered = 0.0;
+            let mut pruned: HashSet<Countable> = HashSet::new();
+
+            for (i, (key, frequency)) in entries.into_iter().enumerate() {
+                let count = count_table.get(key).copied().unwrap_or_default();
+                let keep = self.top_k.is_none_or(|top_k| i < top_k)
+                    && self.min_count.is_none_or(|min_count| count >= min_count)
+                    && self
+                        .min_frequency
+                        .is_none_or(|min_frequency| frequency >= min_frequency)
+                    && self
+                        .coverage
+                        .is_none_or(|coverage| covered < coverage * total);
+                covered += frequency;
+
+                if !keep {
+                    pruned.insert(key.clone());
+                }
+            }
+
+            if pruned.is_empty() {
+                continue;
+            }
+
+            let pruned_mass = PrunedMass {
+                entries: pruned.len(),
+                count: pruned.iter().filter_map(|key| count_table.get(key)).sum(),
+                frequency: pruned
+                    .iter()
+                    .filter_map(|key| frequency_table.get(key))
+                    .sum(),
+            };
+            count_table.retain(|key, _| !pruned.contains(key));
+            frequency_table.retain(|key, _| !pruned.contains(key));
+            pruned_masses.insert(name, pruned_mass);
+        }
+
+        pruned_masses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune() {
+        let mut counts = OccuranceAnalysis::<usize>::default();
+        for (key, count) in [("e", 50), ("t", 30), ("a", 15), ("q", 4), ("z", 1)] {
+            counts.words.increment(key.into(), count);
+        }
+        let mut frequencies: OccuranceAnalysis<f64> = counts.clone() * 1.0;
+        frequencies.normalize();
+
+        let coverage = ReportPruning {
+            coverage: Some(0.9),
+            ..Default::default()
+        };
+        let (mut pruned_counts, mut pruned_frequencies) = (counts.clone(), frequencies.clone());
+        let pruned = coverage.prune(&mut pruned_counts, &mut pruned_frequencies);
+        assert_eq!(pruned_frequencies.words.len(), 3);
+        assert_eq!(pruned["words"].entries, 2);
+        assert_eq!(pruned["words"].count, 5);
+        assert!((pruned["words"].frequency - 0.05).abs() < 1e-9);
+
+        let top_counts = ReportPruning {
+            min_count: Some(10),
+            top_k: Some(4),
+            ..Default::default()
+        };
+        let pruned = top_counts.prune(&mut counts, &mut frequencies);
+        assert_eq!(counts.words.keys().collect::<Vec<_>>(), ["e", "t", "a"]);
+        assert_eq!(frequencies.words.len(), 3);
+        assert!(!pruned.contains_key("ngrams/1"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Units that sentences are split into before counting
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Segmentation {
+    /// Extended grapheme clusters, such that combining sequences and emoji are single units
+    #[default]
+    Grapheme,
+    /// Unicode scalar values
+    CodePoint,
+    /// Bytes of the UTF-8 encoding. Bytes outside ASCII are written as `<XX>` in hex
+    Byte,
+}
+
+impl Segmentation {
+    /// Splits text into units
+    pub fn units<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match self {
+            Segmentation::Grapheme => text.graphemes(true).collect(),
+            Segmentation::CodePoint => text
+                .char_indices()
+                .map(|(i, c)| &text[i..i + c.len_utf8()])
+                .collect(),
+            Segmentation::Byte => text.bytes().map(byte_unit).collect(),
+        }
+    }
+}
+
+/// Unit of a single byte. ASCII bytes are their character
+fn byte_unit(byte: u8) -> &'static str {
+    static BYTE_UNITS: OnceLock<Vec<String>> = OnceLock::new();
+
+    let units = BYTE_UNITS.get_or_init(|| {
+        (0..=u8::MAX)
+            .map(|byte| match byte.is_ascii() {
+                true => (byte as char).to_string(),
+                false => format!("<{byte:02X}>"),
+            })
+            .collect()
+    });
+
+    &units[byte as usize]
+}
+
+/// Unicode normalisation form that sentences are brought into before segmentation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility composition, such that ligatures and full-width forms become plain letters
+    Nfkc,
+}
+
+impl Normalization {
+    pub fn normalize(&self, text: &str) -> String {
+        match self {
+            Normalization::Nfc => text.nfc().collect(),
+            Normalization::Nfd => text.nfd().collect(),
+            Normalization::Nfkc => text.nfkc().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segmentation() {
+        let text = "e\u{301}👍🏽";
+
+        assert_eq!(Segmentation::Grapheme.units(text), vec!["e\u{301}", "👍🏽"]);
+        assert_eq!(
+            Segmentation::CodePoint.units(text),
+            vec!["e", "\u{301}", "👍", "🏽"]
+        );
+        assert_eq!(Segmentation::Byte.units("aé"), vec!["a", "<C3>", "<A9>"]);
+
+        let composed = Normalization::Nfc.normalize(text);
+        assert_eq!(Segmentation::CodePoint.units(&composed)[0], "é");
+        assert_eq!(Normalization::Nfkc.normalize("ﬁ"), "fi");
+        assert_eq!(Normalization::Nfd.normalize("é"), "e\u{301}");
+    }
+}
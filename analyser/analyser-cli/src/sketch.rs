@@ -0,0 +1,197 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    ops::AddAssign,
+};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::occurance::{Countable, Occurances};
+
+/// Error bounds of approximately counted ngrams, by the order of the ngrams
+pub type Approximations = IndexMap<usize, ApproximationBounds>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApproximationBounds {
+    /// Number of entries kept
+    pub capacity: usize,
+    /// Number of ngrams counted
+    pub total: usize,
+    /// Counts exceed the true counts by at most this much.
+    /// Entries occurring more than `total / capacity` times are guaranteed to be kept.
+    pub max_error: usize,
+}
+
+impl AddAssign for ApproximationBounds {
+    /// Combines the bounds of two approximate counts that are summed
+    fn add_assign(&mut self, other: Self) {
+        self.capacity = self.capacity.max(other.capacity);
+        self.total += other.total;
+        self.max_error += other.max_error;
+    }
+}
+
+/// Adds the bounds of another analysis, for when its counts are added
+pub fn add_approximations(approximations: &mut Approximations, other: Approximations) {
+    for (n, bounds) in other {
+        approximations
+            .entry(n)
+            .and_modify(|entry| *entry += bounds.clone())
+            .or_insert(bounds);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    count: usize,
+    error: usize,
+}
+
+/// Space-saving summary, approximately counting the `capacity` most frequent entries of a stream
+/// in constant memory. When full, the least frequent entry is replaced, and the new entry inherits
+/// its count as a possible overestimate.
+#[derive(Debug, Clone)]
+pub struct SpaceSaving {
+    capacity: usize,
+    total: usize,
+    estimates: HashMap<Countable, Estimate>,
+    by_count: BTreeSet<(usize, Countable)>,
+}
+
+impl SpaceSaving {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            total: 0,
+            estimates: HashMap::new(),
+            by_count: BTreeSet::new(),
+        }
+    }
+
+    /// Smallest count that an entry missing from the summary may have had
+    fn min_count(&self) -> usize {
+        if self.estimates.len() < self.capacity {
+            return 0;
+        }
+
+        self.by_count.first().map_or(0, |(count, _)| *count)
+    }
+
+    pub fn insert(&mut self, key: Countable, count: usize) {
+        self.total += count;
+
+        if let Some(estimate) = self.estimates.get_mut(&key) {
+            self.by_count.remove(&(estimate.count, key.clone()));
+            estimate.count += count;
+            self.by_count.insert((estimate.count, key));
+            return;
+        }
+
+        let mut estimate = Estimate { count, error: 0 };
+        if self.estimates.len() >= self.capacity {
+            if let Some((min_count, evicted)) = self.by_count.pop_first() {
+                self.estimates.remove(&evicted);
+                estimate = Estimate {
+                    count: min_count + count,
+                    error: min_count,
+                };
+            }
+        }
+
+        self.by_count.insert((estimate.count, key.clone()));
+        self.estimates.insert(key, estimate);
+    }
+
+    /// Combines the summaries of two parts of a stream.
+    /// Entries missing from one summary are assumed to have occurred as often as its least
+    /// frequent entry, keeping counts overestimates.
+    pub fn merge(&mut self, other: SpaceSaving) {
+        let self_min = self.min_count();
+        let other_min = other.min_count();
+        let missing = |min| Estimate {
+            count: min,
+            error: min,
+        };
+
+        let mut merged: Vec<(Countable, Estimate)> = Vec::new();
+        for (key, estimate) in &self.estimates {
+            let other_estimate = other
+                .estimates
+                .get(key)
+                .copied()
+                .unwrap_or(missing(other_min));
+            merged.push((
+                key.clone(),
+                Estimate {
+                    count: estimate.count + other_estimate.count,
+                    error: estimate.error + other_estimate.error,
+                },
+            ));
+        }
+        for (key, estimate) in other.estimates {
+            if !self.estimates.contains_key(&key) {
+                merged.push((
+                    key,
+                    Estimate {
+                        count: estimate.count + self_min,
+                        error: estimate.error + self_min,
+                    },
+                ));
+            }
+        }
+
+        merged.sort_by_key(|(_, estimate)| Reverse(estimate.count));
+        merged.truncate(self.capacity);
+
+        self.total += other.total;
+        self.by_count = merged
+            .iter()
+            .map(|(key, estimate)| (estimate.count, key.clone()))
+            .collect();
+        self.estimates = merged.into_iter().collect();
+    }
+
+    /// Gets the approximate counts along with their error bounds
+    pub fn finish(self) -> (Occurances<usize>, ApproximationBounds) {
+        let bounds = ApproximationBounds {
+            capacity: self.capacity,
+            total: self.total,
+            max_error: self.estimates.values().map(|e| e.error).max().unwrap_or(0),
+        };
+        let occurances = self
+            .estimates
+            .into_iter()
+            .map(|(key, estimate)| (key, estimate.count))
+            .collect();
+
+        (occurances, bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_saving() {
+        let stream = "aaaaaaaabbbbbbccde";
+        let mut first = SpaceSaving::new(3);
+        let mut second = SpaceSaving::new(3);
+
+        for (i, c) in stream.chars().enumerate() {
+            let sketch = if i % 2 == 0 { &mut first } else { &mut second };
+            sketch.insert(c.to_string().into(), 1);
+        }
+        first.merge(second);
+
+        let (occurances, bounds) = first.finish();
+        let count = |key: &str| *occurances.get(&key.into()).unwrap();
+
+        assert_eq!(occurances.len(), 3);
+        assert_eq!(bounds.total, stream.len());
+        assert!(count("a") >= 8 && count("a") <= 8 + bounds.max_error);
+        assert!(count("b") >= 6 && count("b") <= 6 + bounds.max_error);
+        assert!(bounds.max_error <= bounds.total / bounds.capacity);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+};
+
+use eyre::{eyre, Result};
+use roxmltree::{Document, Node};
+use url::Url;
+use zip::ZipArchive;
+
+use crate::sources::markup::html_to_text;
+
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// The text and metadata of an EPUB book
+#[derive(Debug)]
+pub struct Book {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub rights: Option<String>,
+
+    /// Visible text of each document in the spine, in reading order
+    pub chapters: Vec<String>,
+}
+
+impl Book {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        Self::from_reader(reader)
+    }
+
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)?;
+
+        // The container points to the package document, which holds metadata, manifest and spine
+        let container = read_entry(&mut archive, CONTAINER_PATH)?;
+        let container = Document::parse(&container)?;
+        let package_path = container
+            .descendants()
+            .find(|n| n.has_tag_name("rootfile"))
+            .and_then(|n| n.attribute("full-path"))
+            .ok_or_else(|| eyre!("EPUB container does not reference a package document"))?
+            .to_owned();
+
+        let package = read_entry(&mut archive, &package_path)?;
+        let package = Document::parse(&package)?;
+
+        let chapter_paths: Vec<String> = spine_hrefs(&package)?
+            .into_iter()
+            .map(|href| resolve_href(&package_path, &href))
+            .collect::<Result<_>>()?;
+
+        let chapters = chapter_paths
+            .iter()
+            .map(|path| Ok(html_to_text(&read_entry(&mut archive, path)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            title: metadata_text(&package, "title"),
+            author: metadata_text(&package, "creator"),
+            rights: metadata_text(&package, "rights"),
+            chapters,
+        })
+    }
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, path: &str) -> Result<String> {
+    let mut entry = archive
+        .by_name(path)
+        .map_err(|_| eyre!("EPUB is missing '{path}'"))?;
+
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+
+    Ok(content)
+}
+
+/// Gets the text of the first Dublin Core element with the given name
+fn metadata_text(package: &Document, name: &str) -> Option<String> {
+    let metadata = package.descendants().find(|n| n.has_tag_name("metadata"))?;
+    let text = metadata
+        .descendants()
+        .find(|n| n.has_tag_name(name))?
+        .text()?
+        .trim();
+
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// Gets the manifest hrefs of the linear spine items in reading order
+fn spine_hrefs(package: &Document) -> Result<Vec<String>> {
+    let find = |name: &str| -> Result<Node> {
+        package
+            .descendants()
+            .find(|n| n.has_tag_name(name))
+            .ok_or_else(|| eyre!("EPUB package document has no {name}"))
+    };
+
+    let manifest = find("manifest")?;
+    let spine = find("spine")?;
+
+    spine
+        .children()
+        .filter(|n| n.has_tag_name("itemref") && n.attribute("linear") != Some("no"))
+        .map(|itemref| {
+            let idref = itemref
+                .attribute("idref")
+                .ok_or_else(|| eyre!("EPUB spine item has no idref"))?;
+
+            manifest
+                .children()
+                .find(|n| n.has_tag_name("item") && n.attribute("id") == Some(idref))
+                .and_then(|item| item.attribute("href"))
+                .map(|href| href.to_owned())
+                .ok_or_else(|| eyre!("EPUB spine references unknown item '{idref}'"))
+        })
+        .collect()
+}
+
+/// Resolves an href relative to the package document into a path within the archive
+fn resolve_href(package_path: &str, href: &str) -> Result<String> {
+    let base = Url::parse("file:///")?.join(package_path)?;
+    let mut resolved = base.join(href)?;
+    resolved.set_fragment(None);
+
+    let path = resolved
+        .to_file_path()
+        .map_err(|_| eyre!("Invalid href in EPUB: '{href}'"))?;
+
+    Ok(path.to_string_lossy().trim_start_matches('/').to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    #[test]
+    fn test_book_from_reader() {
+        #[rustfmt::skip]
+        let files = [
+            (
+                "META-INF/container.xml",
+                r#"<?xml version="1.0"?>
+                <container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0">
+                  <rootfiles>
+                    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+                  </rootfiles>
+                </container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<?xml version="1.0"?>
+                <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:title>Eventyr</dc:title>
+                    <dc:creator>H.C. Andersen</dc:creator>
+                  </metadata>
+                  <manifest>
+                    <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="c2" href="text/ch%202.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+                  </manifest>
+                  <spine>
+                    <itemref idref="cover" linear="no"/>
+                    <itemref idref="c1"/>
+                    <itemref idref="c2"/>
+                  </spine>
+                </package>"#,
+            ),
+            ("OEBPS/cover.xhtml", "<html><body><p>Omslag</p></body></html>"),
+            ("OEBPS/text/ch1.xhtml", "<html><body><p>Første kapitel</p></body></html>"),
+            ("OEBPS/text/ch 2.xhtml", "<html><body><p>Andet kapitel</p></body></html>"),
+        ];
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+
+        let book = Book::from_reader(Cursor::new(archive.into_inner())).unwrap();
+
+        assert_eq!(book.title.as_deref(), Some("Eventyr"));
+        assert_eq!(book.author.as_deref(), Some("H.C. Andersen"));
+        assert_eq!(book.rights, None);
+        assert_eq!(book.chapters.len(), 2);
+        assert_eq!(book.chapters[0].trim(), "Første kapitel");
+        assert_eq!(book.chapters[1].trim(), "Andet kapitel");
+    }
+}
@@ -0,0 +1,182 @@
+//! Keystroke logs record what was actually typed, including corrections and shortcuts.
+//!
+//! The format is plain text with one key event per line:
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! <timestamp in milliseconds> <down|up> <key>
+//! 1697712000000 down Shift
+//! 1697712000080 down h
+//! 1697712000120 up h
+//! 1697712000130 up Shift
+//! 1697712000300 down Backspace
+//! ```
+//!
+//! Keys producing a single character are given as that character, such as `a`, `ø` or `,`.
+//! Other keys are given by name, such as `Space`, `Enter`, `Tab`, `Backspace`, `Delete`,
+//! `Escape`, `Shift`, `Control`, `Alt`, `AltGr`, `Meta`, `CapsLock`, `Left`, `Right`, `Up`,
+//! `Down`, `Home`, `End`, `PageUp` and `PageDown`.
+//!
+//! Logs found in directories are read if their extension is `.keylog`.
+
+use std::{collections::HashSet, path::Path};
+
+use eyre::{eyre, Result};
+
+use crate::occurance::Countable;
+
+#[derive(Debug, PartialEq)]
+pub struct KeyEvent {
+    pub timestamp: u64,
+    pub pressed: bool,
+    pub key: Countable,
+}
+
+/// Whether a file found in a directory is a keystroke log
+pub fn is_keylog(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("keylog"))
+}
+
+/// Parses the events of a keystroke log
+pub fn parse_keylog(content: &str) -> Result<Vec<KeyEvent>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let invalid = || eyre!("Invalid key event on line {}: '{line}'", i + 1);
+            let mut fields = line.splitn(3, char::is_whitespace);
+
+            let timestamp = fields
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            let pressed = match fields.next().ok_or_else(invalid)? {
+                "down" => true,
+                "up" => false,
+                _ => return Err(invalid()),
+            };
+            let key = key_token(fields.next().ok_or_else(invalid)?.trim());
+
+            Ok(KeyEvent {
+                timestamp,
+                pressed,
+                key,
+            })
+        })
+        .collect()
+}
+
+/// Gets the token representing a key in ngrams.
+/// Character keys are represented by the lowercase character and the space bar by a space,
+/// such that they line up with text analyses. Other keys are represented as `<Name>`.
+pub fn key_token(key: &str) -> Countable {
+    let mut chars = key.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.to_lowercase().collect(),
+        _ if key == "Space" => " ".into(),
+        _ => format!("<{key}>").into(),
+    }
+}
+
+/// Splits key presses into bursts of typing separated by pauses longer than `max_pause` milliseconds.
+/// Repeated presses from holding a key down are ignored. Keys are taken to be released by a
+/// pause, such that logs missing key-up events don't suppress later presses.
+pub fn key_sequences(events: &[KeyEvent], max_pause: u64) -> Vec<Vec<Countable>> {
+    let mut sequences: Vec<Vec<Countable>> = Vec::new();
+    let mut sequence: Vec<Countable> = Vec::new();
+    let mut held: HashSet<&Countable> = HashSet::new();
+    let mut last_timestamp: Option<u64> = None;
+
+    for event in events {
+        if !event.pressed {
+            held.remove(&event.key);
+            continue;
+        }
+
+        let is_pause =
+            last_timestamp.is_some_and(|last| event.timestamp.saturating_sub(last) > max_pause);
+        if is_pause {
+            held.clear();
+            if !sequence.is_empty() {
+                sequences.push(std::mem::take(&mut sequence));
+            }
+        }
+
+        if !held.insert(&event.key) {
+            continue;
+        }
+
+        sequence.push(event.key.clone());
+        last_timestamp = Some(event.timestamp);
+    }
+
+    if !sequence.is_empty() {
+        sequences.push(sequence);
+    }
+
+    sequences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_sequences() {
+        let log = "# session\n\
+            0 down Shift\n10 down H\n20 up H\n30 up Shift\n\
+            40 down e\n45 down e\n50 up e\n\
+            60 down Space\n70 up Space\n\
+            10000 down Backspace\n10010 up Backspace\n";
+
+        let events = parse_keylog(log).unwrap();
+        let sequences = key_sequences(&events, 5000);
+
+        assert_eq!(
+            sequences,
+            vec![
+                vec![
+                    Countable::from("<Shift>"),
+                    Countable::from("h"),
+                    Countable::from("e"),
+                    Countable::from(" "),
+                ],
+                vec![Countable::from("<Backspace>")],
+            ]
+        );
+        assert!(parse_keylog("10 pressed a").is_err());
+    }
+
+    #[test]
+    fn test_key_sequences_missing_key_ups() {
+        let log = "0 down a
+10 down b
+20 down a
+10000 down a
+10010 down b
+";
+
+        let events = parse_keylog(log).unwrap();
+        let sequences = key_sequences(&events, 5000);
+
+        assert_eq!(
+            sequences,
+            vec![
+                vec![Countable::from("a"), Countable::from("b")],
+                vec![Countable::from("a"), Countable::from("b")],
+            ]
+        );
+
+        let error = parse_keylog(
+            "0 down a
+soon down b",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use ego_tree::NodeRef;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use scraper::{Html, Node};
+
+/// Elements whose content is never rendered as text
+const HIDDEN_ELEMENTS: [&str; 8] = [
+    "head", "script", "style", "noscript", "template", "svg", "math", "iframe",
+];
+
+/// Elements that separate their content from the surrounding text
+const BLOCK_ELEMENTS: [&str; 34] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MarkupFormat {
+    Html,
+    Markdown,
+}
+
+impl MarkupFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the visible text of a markup document.
+/// Blocks are separated by newlines.
+pub fn extract_text(content: &str, format: MarkupFormat, keep_code: bool) -> String {
+    match format {
+        MarkupFormat::Html => html_to_text(content),
+        MarkupFormat::Markdown => markdown_to_text(content, keep_code),
+    }
+}
+
+pub fn html_to_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let mut text = String::new();
+    collect_html_text(document.tree.root(), &mut text);
+
+    text
+}
+
+fn collect_html_text(node: NodeRef<Node>, text: &mut String) {
+    match node.value() {
+        Node::Text(content) => text.push_str(content),
+        Node::Element(element) => {
+            let name = element.name();
+            if HIDDEN_ELEMENTS.contains(&name) {
+                return;
+            }
+
+            let is_block = BLOCK_ELEMENTS.contains(&name);
+            if is_block {
+                text.push('\n');
+            }
+
+            node.children()
+                .for_each(|child| collect_html_text(child, text));
+
+            if is_block {
+                text.push('\n');
+            }
+        }
+        Node::Document | Node::Fragment => {
+            node.children()
+                .for_each(|child| collect_html_text(child, text));
+        }
+        _ => {}
+    }
+}
+
+pub fn markdown_to_text(markdown: &str, keep_code: bool) -> String {
+    let mut text = String::new();
+    let mut in_code_block = false;
+    let mut in_image = false;
+
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                text.push('\n');
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                in_code_block = false;
+                text.push('\n');
+            }
+            Event::Start(Tag::Image(..)) => in_image = true,
+            Event::End(Tag::Image(..)) => in_image = false,
+            Event::End(
+                Tag::Paragraph
+                | Tag::Heading(..)
+                | Tag::Item
+                | Tag::TableCell
+                | Tag::TableRow
+                | Tag::TableHead
+                | Tag::FootnoteDefinition(_),
+            ) => text.push('\n'),
+            Event::Text(content) => {
+                if in_image || (in_code_block && !keep_code) {
+                    continue;
+                }
+                text.push_str(&content);
+            }
+            Event::Code(content) => {
+                if keep_code {
+                    text.push_str(&content);
+                }
+            }
+            Event::Html(content) => text.push_str(&html_to_text(&content)),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak | Event::Rule => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::split_sentences;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>T</title><style>p { x: 1 }</style></head>\
+            <body><p>Fisk &amp; chips&nbsp;&lt;3</p><script>var a = 1 < 2;</script>\
+            <div>Rødgrød med <b>fløde</b></div>\
+            <table><tr><th>Ret</th><th>Pris</th></tr><tr><td>Æbleskiver</td><td>40</td></tr></table>\
+            </body></html>";
+
+        let sentences = split_sentences(&html_to_text(html));
+
+        assert_eq!(
+            sentences,
+            vec![
+                "Fisk & chips <3",
+                "Rødgrød med fløde",
+                "Ret",
+                "Pris",
+                "Æbleskiver",
+                "40"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let markdown = "# Title\n\nSee [the docs](https://example.com) and ![logo](logo.png).\n\n\
+            ```rust\nlet x = 1;\n```\n\nUse `cargo`.\n";
+
+        let without_code = split_sentences(&markdown_to_text(markdown, false));
+        let with_code = split_sentences(&markdown_to_text(markdown, true));
+
+        assert_eq!(without_code, vec!["Title", "See the docs and .", "Use ."]);
+        assert_eq!(
+            with_code,
+            vec!["Title", "See the docs and .", "let x = 1;", "Use cargo."]
+        );
+    }
+}
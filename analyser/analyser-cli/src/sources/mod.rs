@@ -0,0 +1,18 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+pub mod epub;
+pub mod git;
+pub mod keylog;
+pub mod markup;
+pub mod wet;
+pub mod wortschatz;
+
+/// Splits extracted text into sentences.
+/// Lines are treated as hard boundaries, within lines UAX #29 sentence boundaries are used.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| line.unicode_sentences())
+        .map(|sentence| sentence.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
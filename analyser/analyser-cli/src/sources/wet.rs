@@ -0,0 +1,268 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use eyre::{eyre, Result};
+use flate2::read::MultiGzDecoder;
+use indexmap::IndexMap;
+
+const LANGUAGE_HEADER: &str = "WARC-Identified-Content-Language";
+
+/// A single record of a WARC file
+#[derive(Debug)]
+pub struct WarcRecord {
+    pub headers: IndexMap<String, String>,
+    pub content: String,
+}
+
+impl WarcRecord {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether this is a record holding extracted page text, as opposed to `warcinfo` and the like
+    pub fn is_conversion(&self) -> bool {
+        self.header("WARC-Type") == Some("conversion")
+    }
+
+    /// Languages identified by Common Crawl, most prominent first
+    pub fn languages(&self) -> Vec<&str> {
+        self.header(LANGUAGE_HEADER)
+            .map(|languages| languages.split(',').map(|l| l.trim()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Iterates over the records of a WARC file, such as the WET files from Common Crawl
+pub struct WarcReader<R: BufRead> {
+    reader: R,
+}
+
+impl WarcReader<Box<dyn BufRead>> {
+    /// Opens a WARC file, decompressing it if it ends in `.gz`
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let is_gzip = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gz"));
+
+        let reader: Box<dyn BufRead> = if is_gzip {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+
+        Ok(Self::new(reader))
+    }
+}
+
+impl<R: BufRead> WarcReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    fn read_record(&mut self) -> Result<Option<WarcRecord>> {
+        let mut line = String::new();
+
+        // Skip blank lines separating records
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+
+        if !line.starts_with("WARC/") {
+            return Err(eyre!("Expected WARC record, found '{}'", line.trim()));
+        }
+
+        let mut headers = IndexMap::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(eyre!("Unexpected end of WARC file in record header"));
+            }
+
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((key, value)) = header.split_once(':') {
+                headers.insert(key.trim().to_owned(), value.trim().to_owned());
+            }
+        }
+
+        let mut record = WarcRecord {
+            headers,
+            content: String::new(),
+        };
+        let content_length: u64 = record
+            .header("Content-Length")
+            .ok_or_else(|| eyre!("WARC record has no Content-Length"))?
+            .parse()?;
+
+        let mut content = Vec::new();
+        (&mut self.reader)
+            .take(content_length)
+            .read_to_end(&mut content)?;
+        record.content = String::from_utf8_lossy(&content).into_owned();
+
+        Ok(Some(record))
+    }
+}
+
+impl<R: BufRead> Iterator for WarcReader<R> {
+    type Item = Result<WarcRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Heuristics to remove boilerplate and noise from web text
+#[derive(Debug, Clone)]
+pub struct QualityFilter {
+    /// Lines with fewer characters are dropped
+    pub min_line_length: usize,
+    /// Lines where a larger fraction of the non-whitespace characters are not letters are dropped
+    pub max_symbol_ratio: f64,
+    /// Documents where a larger fraction of the lines are repeats are dropped
+    pub max_line_repetition: f64,
+    /// If not empty, only documents identified as primarily one of these languages are kept
+    pub languages: Vec<String>,
+}
+
+impl Default for QualityFilter {
+    fn default() -> Self {
+        Self {
+            min_line_length: 30,
+            max_symbol_ratio: 0.25,
+            max_line_repetition: 0.3,
+            languages: Vec::new(),
+        }
+    }
+}
+
+impl QualityFilter {
+    /// Gets the lines of the document that pass the filter.
+    /// Returns `None` if the document as a whole is rejected.
+    pub fn filter<'a>(&self, record: &'a WarcRecord) -> Option<Vec<&'a str>> {
+        if !self.languages.is_empty() {
+            let language = *record.languages().first()?;
+            if !self.languages.iter().any(|l| l == language) {
+                return None;
+            }
+        }
+
+        let lines: Vec<&str> = record
+            .content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        if line_repetition(&lines) > self.max_line_repetition {
+            return None;
+        }
+
+        let kept: Vec<&str> = lines
+            .into_iter()
+            .filter(|line| line.chars().count() >= self.min_line_length)
+            .filter(|line| symbol_ratio(line) <= self.max_symbol_ratio)
+            .collect();
+
+        (!kept.is_empty()).then_some(kept)
+    }
+}
+
+/// Fraction of lines that are repeats of an earlier line
+pub fn line_repetition(lines: &[&str]) -> f64 {
+    if lines.is_empty() {
+        return 0.0;
+    }
+
+    let unique: HashSet<&&str> = lines.iter().collect();
+    (lines.len() - unique.len()) as f64 / lines.len() as f64
+}
+
+/// Fraction of non-whitespace characters that are not letters
+pub fn symbol_ratio(line: &str) -> f64 {
+    let (symbols, total) = line
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .fold((0, 0), |(symbols, total), c| {
+            (symbols + usize::from(!c.is_alphabetic()), total + 1)
+        });
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    symbols as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(content: &str, language: &str) -> String {
+        format!(
+            "WARC/1.0\r\nWARC-Type: conversion\r\n{LANGUAGE_HEADER}: {language}\r\n\
+            Content-Length: {}\r\n\r\n{content}\r\n\r\n",
+            content.len()
+        )
+    }
+
+    #[test]
+    fn test_warc_reader() {
+        let info = "WARC/1.0\r\nWARC-Type: warcinfo\r\ncontent-length: 5\r\n\r\nhello\r\n\r\n";
+        let warc = [info, &record("Første linje\nAnden linje", "dan,eng")].concat();
+
+        let records: Vec<WarcRecord> = WarcReader::new(warc.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert!(!records[0].is_conversion());
+        assert!(records[1].is_conversion());
+        assert_eq!(records[1].languages(), vec!["dan", "eng"]);
+        assert_eq!(records[1].content, "Første linje\nAnden linje");
+    }
+
+    #[test]
+    fn test_quality_filter() {
+        let filter = QualityFilter {
+            min_line_length: 10,
+            max_symbol_ratio: 0.2,
+            max_line_repetition: 0.3,
+            languages: vec!["dan".to_owned()],
+        };
+
+        let good = "Dette er en fin sætning.\nKort\n| 1 | 2 | 3 | 4 | 5 |\nOgså en god linje her.";
+        let repetitive = "Accepter cookies nu\nAccepter cookies nu\nAccepter cookies nu\nHej";
+
+        let text = |content: &str, language: &str| {
+            WarcReader::new(record(content, language).as_bytes())
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+
+        assert_eq!(
+            filter.filter(&text(good, "dan")),
+            Some(vec!["Dette er en fin sætning.", "Også en god linje her."])
+        );
+        assert_eq!(filter.filter(&text(good, "eng,dan")), None);
+        assert_eq!(filter.filter(&text(repetitive, "dan")), None);
+    }
+}
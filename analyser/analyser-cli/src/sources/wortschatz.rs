@@ -0,0 +1,191 @@
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Where a sentence of a Wortschatz corpus was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenceSource {
+    /// Host of the source URL, without `www.`
+    pub domain: Option<String>,
+    pub year: Option<i32>,
+}
+
+impl SentenceSource {
+    /// Parses a line of a sources file, of the form `id<TAB>url<TAB>date`
+    fn parse(line: &str) -> Option<(u64, Self)> {
+        let mut columns = line.split('\t');
+        let id = columns.next()?.trim().parse().ok()?;
+        let url = columns.next().unwrap_or_default().trim();
+        let date = columns.next().unwrap_or_default().trim();
+
+        let domain = Url::parse(url).ok().and_then(|url| {
+            let host = url.host_str()?.to_lowercase();
+            Some(host.trim_start_matches("www.").to_owned())
+        });
+        let year = date.get(..4).and_then(|year| year.parse().ok());
+
+        Some((id, Self { domain, year }))
+    }
+}
+
+/// Links the ids of sentences to their sources, from the contents of the sources file and the
+/// inverse sources file. Sentences found in several sources are linked to the first.
+pub fn parse_sentence_sources(
+    sources: &str,
+    inverse_sources: &str,
+) -> HashMap<u64, SentenceSource> {
+    let sources: HashMap<u64, SentenceSource> =
+        sources.lines().filter_map(SentenceSource::parse).collect();
+
+    let mut sentence_sources = HashMap::new();
+    for line in inverse_sources.lines() {
+        let Some((source_id, sentence_id)) = line.split_once('\t') else {
+            continue;
+        };
+        let (Ok(source_id), Ok(sentence_id)) = (
+            source_id.trim().parse::<u64>(),
+            sentence_id.trim().parse::<u64>(),
+        ) else {
+            continue;
+        };
+
+        if let Some(source) = sources.get(&source_id) {
+            sentence_sources
+                .entry(sentence_id)
+                .or_insert_with(|| source.clone());
+        }
+    }
+
+    sentence_sources
+}
+
+/// Reads the sources of the sentences of a fetched corpus
+pub fn read_sentence_sources(corpus_directory: &Path) -> Result<HashMap<u64, SentenceSource>> {
+    let sources_path = corpus_directory.join("sources.txt");
+    let inverse_sources_path = corpus_directory.join("inv_so.txt");
+
+    if !sources_path.exists() || !inverse_sources_path.exists() {
+        return Err(eyre!(
+            "Could not open the sources of '{}'. Maybe you need to fetch it again with --force?",
+            corpus_directory.display()
+        ));
+    }
+
+    Ok(parse_sentence_sources(
+        &read_to_string(sources_path)?,
+        &read_to_string(inverse_sources_path)?,
+    ))
+}
+
+/// Selects sentences by the source they were found in.
+/// Sentences of unknown source are only kept if no domains or years are asked for.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SourceSelection {
+    /// Keep only sentences from these domains or their subdomains
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+
+    /// Drop sentences from these domains or their subdomains
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_domains: Vec<String>,
+
+    /// Keep only sentences published in these years
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub years: Vec<i32>,
+}
+
+impl SourceSelection {
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty() && self.excluded_domains.is_empty() && self.years.is_empty()
+    }
+
+    pub fn matches(&self, source: Option<&SentenceSource>) -> bool {
+        let domain = source.and_then(|source| source.domain.as_deref());
+        let year = source.and_then(|source| source.year);
+        let in_domains = |domains: &[String]| {
+            domain.is_some_and(|domain| domains.iter().any(|d| is_within_domain(domain, d)))
+        };
+
+        (self.domains.is_empty() || in_domains(&self.domains))
+            && !in_domains(&self.excluded_domains)
+            && (self.years.is_empty() || year.is_some_and(|year| self.years.contains(&year)))
+    }
+}
+
+fn is_within_domain(domain: &str, parent: &str) -> bool {
+    let parent = parent.trim_start_matches("www.");
+    domain == parent || domain.ends_with(&format!(".{parent}"))
+}
+
+/// Splits a corpus into an analysis for each value of a property of the sources of its sentences
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SourcePartition {
+    Domain,
+    Year,
+}
+
+impl SourcePartition {
+    /// Gets the partition of a sentence, which is unknown for sentences of unknown source
+    pub fn key(&self, source: &SentenceSource) -> Option<String> {
+        match self {
+            SourcePartition::Domain => source.domain.clone(),
+            SourcePartition::Year => source.year.map(|year| year.to_string()),
+        }
+    }
+
+    /// Narrows a selection to the sentences of a single partition
+    pub fn select(&self, selection: &SourceSelection, key: &str) -> SourceSelection {
+        let mut selection = selection.clone();
+        match self {
+            SourcePartition::Domain => selection.domains = vec![key.to_owned()],
+            SourcePartition::Year => selection.years = key.parse().into_iter().collect(),
+        }
+
+        selection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentence_sources() {
+        let sources = "1\thttp://www.dr.dk/nyheder/1\t2021-03-01\n\
+                       2\thttps://sport.tv2.dk/a\t2020-12-31\n\
+                       3\tnot a url\t\n";
+        let inverse_sources = "1\t10\n2\t11\n1\t11\n3\t12\n";
+
+        let sentence_sources = parse_sentence_sources(sources, inverse_sources);
+        assert_eq!(sentence_sources[&10].domain.as_deref(), Some("dr.dk"));
+        assert_eq!(
+            sentence_sources[&11].domain.as_deref(),
+            Some("sport.tv2.dk")
+        );
+        assert_eq!(sentence_sources[&12].year, None);
+
+        let news_2021 = SourceSelection {
+            years: vec![2021],
+            ..Default::default()
+        };
+        assert!(news_2021.matches(sentence_sources.get(&10)));
+        assert!(!news_2021.matches(sentence_sources.get(&11)));
+        assert!(!news_2021.matches(None));
+
+        let without_tv2 = SourceSelection {
+            excluded_domains: vec!["tv2.dk".to_owned()],
+            ..Default::default()
+        };
+        assert!(without_tv2.matches(sentence_sources.get(&10)));
+        assert!(!without_tv2.matches(sentence_sources.get(&11)));
+        assert!(without_tv2.matches(None));
+
+        assert_eq!(
+            SourcePartition::Year.key(&sentence_sources[&11]),
+            Some("2020".to_owned())
+        );
+    }
+}
@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::analyse::{SkipgramShape, WORD_SEPARATOR};
 use crate::occurance::{Countable, OccuranceAnalysis, OccuranceT, Occurances};
 
 impl<T: OccuranceT> Occurances<T> {
@@ -19,19 +25,38 @@ impl<T: OccuranceT> Occurances<T> {
 
 impl<T: OccuranceT> OccuranceAnalysis<T> {
     pub fn strip(&mut self, check: impl Fn(&Countable) -> (bool, Countable)) {
-        // For ngrams and skipgrams we just strip invalid entries
+        // For ngrams, skipgrams and positions we just strip invalid entries
         self.ngrams
             .values_mut()
             .chain(self.skipgrams.values_mut())
+            .chain(self.shaped_skipgrams.values_mut())
+            .chain(self.positions.values_mut())
             .for_each(|occurance| {
                 occurance.strip(false, &check);
             });
 
         // For words we strip invalid entries and replace them with the stripped version
         self.words.strip(true, &check);
+
+        // Word ngrams are stripped word by word, dropping those with a word stripped entirely
+        self.word_ngrams.values_mut().for_each(|occurance| {
+            occurance.strip(true, |ngram| {
+                let checked: Vec<(bool, Countable)> = ngram
+                    .split(WORD_SEPARATOR)
+                    .map(|word| check(&word.into()))
+                    .collect();
+                let strip = checked.iter().any(|(strip, _)| *strip);
+                if checked.iter().any(|(_, word)| word.is_empty()) {
+                    return (strip, Countable::new());
+                }
+
+                let words: Vec<&str> = checked.iter().map(|(_, word)| word.as_str()).collect();
+                (strip, words.join(WORD_SEPARATOR).into())
+            });
+        });
     }
 
-    pub fn transform(&mut self, spec: &TransformSpecification) {
+    pub fn transform(&mut self, spec: &TransformSpecification) -> Result<()> {
         if spec.strip_whitespace {
             self.strip(check_whitespace);
         }
@@ -47,6 +72,134 @@ impl<T: OccuranceT> OccuranceAnalysis<T> {
         if spec.strip_nonlatin {
             self.strip(check_nonlatin);
         }
+
+        if let Some(keystrokes) = &spec.keystrokes {
+            *self = self.to_keystrokes(keystrokes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-expresses the analysis in terms of the keystrokes needed to type it.
+    /// Keystroke ngrams of order n are derived from the ngrams of order n,
+    /// keystroke skipgrams of order n from the ngrams of order n + 2 and shaped skipgrams from
+    /// the ngrams spanning their shape. Fails if those ngrams were not counted.
+    /// Entries containing characters without keystrokes are dropped.
+    /// Lengths are kept as they are, counting characters and words rather than keystrokes.
+    pub fn to_keystrokes(&self, keystrokes: &HashMap<Countable, Vec<Countable>>) -> Result<Self> {
+        let mut result = Self {
+            lengths: self.lengths.clone(),
+            num_sentences: self.num_sentences,
+            ..Default::default()
+        };
+
+        let missing_ngrams = |n: usize, table: &str| {
+            eyre!("Keystroke {table} are derived from ngrams of order {n}, which were not counted. Analyse the sources with `-n {n}` or higher")
+        };
+        for &k in self.skipgrams.keys() {
+            if !self.ngrams.contains_key(&(k + 2)) {
+                return Err(missing_ngrams(k + 2, &format!("skipgrams of order {k}")));
+            }
+        }
+        let mut shapes: Vec<(&String, SkipgramShape)> = Vec::new();
+        for name in self.shaped_skipgrams.keys() {
+            let shape: SkipgramShape = name.parse()?;
+            if !self.ngrams.contains_key(&shape.len()) {
+                return Err(missing_ngrams(
+                    shape.len(),
+                    &format!("skipgrams of shape {name}"),
+                ));
+            }
+            shapes.push((name, shape));
+        }
+
+        let type_gram = |gram: &str| -> Option<(Vec<&Countable>, usize)> {
+            let typed: Vec<&Vec<Countable>> = gram
+                .graphemes(true)
+                .map(|grapheme| keystrokes.get(grapheme).filter(|keys| !keys.is_empty()))
+                .collect::<Option<_>>()?;
+
+            let first_len = typed.first()?.len();
+            Some((typed.into_iter().flatten().collect(), first_len))
+        };
+
+        for (&n, grams) in &self.ngrams {
+            let skip_n = n.checked_sub(2).filter(|k| self.skipgrams.contains_key(k));
+
+            for (gram, &count) in grams.iter() {
+                let Some((keys, first_len)) = type_gram(gram) else {
+                    continue;
+                };
+
+                // Only count windows starting at the first character, as the
+                // following characters start windows of ngrams of their own
+                for start in 0..first_len {
+                    let Some(window) = keys.get(start..start + n) else {
+                        continue;
+                    };
+
+                    let ngram: Countable = window.iter().map(|k| k.as_str()).collect();
+                    result.ngrams.entry(n).or_default().increment(ngram, count);
+
+                    if let Some(skip_n) = skip_n {
+                        let skipgram: Countable =
+                            [window[0].as_str(), window[n - 1].as_str()].concat().into();
+                        result
+                            .skipgrams
+                            .entry(skip_n)
+                            .or_default()
+                            .increment(skipgram, count);
+                    }
+
+                    let window: Vec<&str> = window.iter().map(|k| k.as_str()).collect();
+                    for (name, shape) in shapes.iter().filter(|(_, shape)| shape.len() == n) {
+                        result
+                            .shaped_skipgrams
+                            .entry((*name).clone())
+                            .or_default()
+                            .increment(shape.key(&window), count);
+                    }
+                }
+            }
+        }
+
+        let type_occurances = |occurances: &Occurances<T>| -> Occurances<T> {
+            let mut typed_occurances = Occurances::new();
+            for (gram, &count) in occurances.iter() {
+                if let Some((keys, _)) = type_gram(gram) {
+                    let typed: Countable = keys.iter().map(|k| k.as_str()).collect();
+                    typed_occurances.increment(typed, count);
+                }
+            }
+
+            typed_occurances
+        };
+
+        // Words and positions are kept whole, but spelled out in keystrokes
+        result.words = type_occurances(&self.words);
+        for (&n, word_ngrams) in &self.word_ngrams {
+            let mut typed_word_ngrams = Occurances::new();
+            for (ngram, &count) in word_ngrams.iter() {
+                let typed: Option<Vec<Countable>> = ngram
+                    .split(WORD_SEPARATOR)
+                    .map(|word| {
+                        let (keys, _) = type_gram(word)?;
+                        Some(keys.iter().map(|k| k.as_str()).collect())
+                    })
+                    .collect();
+
+                if let Some(typed) = typed {
+                    typed_word_ngrams.increment(typed.join(WORD_SEPARATOR).into(), count);
+                }
+            }
+            result.word_ngrams.insert(n, typed_word_ngrams);
+        }
+        result.positions = self
+            .positions
+            .clone()
+            .map(|occurances| type_occurances(&occurances));
+
+        Ok(result)
     }
 }
 
@@ -75,6 +228,7 @@ pub struct TransformSpecification {
     pub strip_punctuation: bool,
     pub strip_numbers: bool,
     pub strip_nonlatin: bool,
+    pub keystrokes: Option<HashMap<Countable, Vec<Countable>>>,
 }
 
 #[cfg(test)]
@@ -97,4 +251,57 @@ mod tests {
         assert_eq!(occurances_replace.get(&"a".into()), Some(&3));
         assert_eq!(occurances_strip.get(&"a".into()), Some(&1));
     }
+
+    #[test]
+    fn test_to_keystrokes() {
+        let keystrokes: HashMap<Countable, Vec<Countable>> = [
+            ("a", vec!["a"]),
+            ("A", vec!["<Shift>", "a"]),
+            ("é", vec!["´", "e"]),
+        ]
+        .into_iter()
+        .map(|(c, keys)| (c.into(), keys.into_iter().map(Countable::from).collect()))
+        .collect();
+
+        let mut analysis = OccuranceAnalysis::<usize>::default();
+        analysis.ngrams.insert(
+            1,
+            vec![
+                ("a".into(), 2),
+                ("A".into(), 1),
+                ("é".into(), 1),
+                ("?".into(), 5),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        analysis
+            .ngrams
+            .insert(3, vec![("aéA".into(), 1)].into_iter().collect());
+        analysis.skipgrams.insert(1, Occurances::new());
+        analysis
+            .shaped_skipgrams
+            .insert("a_b".to_owned(), Occurances::new());
+
+        let keystroke_analysis = analysis.to_keystrokes(&keystrokes).unwrap();
+        let ngrams1 = &keystroke_analysis.ngrams[&1];
+        let ngrams3 = &keystroke_analysis.ngrams[&3];
+        let skipgrams1 = &keystroke_analysis.skipgrams[&1];
+
+        assert_eq!(ngrams1.get(&"a".into()), Some(&3));
+        assert_eq!(ngrams1.get(&"<Shift>".into()), Some(&1));
+        assert_eq!(ngrams1.get(&"´".into()), Some(&1));
+        assert_eq!(ngrams1.get(&"?".into()), None);
+        assert_eq!(ngrams3.get(&"a´e".into()), Some(&1));
+        assert_eq!(ngrams3.sum(), 1);
+        assert_eq!(skipgrams1.get(&"ae".into()), Some(&1));
+        assert_eq!(
+            keystroke_analysis.shaped_skipgrams["a_b"].get(&"ae".into()),
+            Some(&1)
+        );
+
+        analysis.skipgrams.insert(2, Occurances::new());
+        let error = analysis.to_keystrokes(&keystrokes).unwrap_err();
+        assert!(error.to_string().contains("skipgrams of order 2"));
+    }
 }
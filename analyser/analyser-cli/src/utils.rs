@@ -1,12 +1,267 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use eyre::Result;
-use serde::de::DeserializeOwned;
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use serde::{de::DeserializeOwned, Serialize};
 
+/// First bytes of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Format analyses and reports are stored in, each with its own file extension.
+/// Reading detects the format from the content, such that objects of any format can be read
+/// wherever they are expected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum StorageFormat {
+    /// Pretty-printed JSON
+    #[default]
+    Json,
+    /// MessagePack, with fields stored by name
+    Msgpack,
+    /// MessagePack compressed with zstd
+    MsgpackZstd,
+}
+
+impl StorageFormat {
+    /// File extension of objects stored in the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Msgpack => "msgpack",
+            StorageFormat::MsgpackZstd => "msgpack.zst",
+        }
+    }
+
+    /// Detects the format of stored content by its first bytes
+    pub fn detect(content: &[u8]) -> Self {
+        let first = content.iter().find(|byte| !byte.is_ascii_whitespace());
+
+        if content.starts_with(&ZSTD_MAGIC) {
+            StorageFormat::MsgpackZstd
+        } else if matches!(first, Some(b'{' | b'[')) {
+            StorageFormat::Json
+        } else {
+            StorageFormat::Msgpack
+        }
+    }
+}
+
+/// Path of the object `id` in `directory`, in whichever format it is stored.
+/// Objects that do not exist yet are given the path of a JSON file.
+pub fn find_object(directory: &Path, id: &str) -> PathBuf {
+    StorageFormat::value_variants()
+        .iter()
+        .map(|format| directory.join(format!("{id}.{}", format.extension())))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| directory.join(format!("{id}.json")))
+}
+
+/// Reads an object stored in any `StorageFormat`
 pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let obj: T = serde_json::from_reader(reader)?;
+
+    decode(BufReader::new(file))
+}
+
+/// Writes an object in a `StorageFormat` in place of the object at `path`, which may be stored
+/// in another format. The object is written to a temporary file first and then renamed,
+/// such that the existing object stays intact if writing fails. Returns the path written.
+pub fn write_object<T: Serialize>(path: &Path, obj: &T, format: StorageFormat) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| eyre!("Cannot store an object at '{}'", path.display()))?;
+    let id = StorageFormat::value_variants()
+        .iter()
+        .find_map(|format| file_name.strip_suffix(&format!(".{}", format.extension())))
+        .unwrap_or(file_name);
+    let new_path = path.with_file_name(format!("{id}.{}", format.extension()));
+    let temporary_path = path.with_file_name(format!(".{id}.tmp"));
+
+    let written = File::create(&temporary_path)
+        .map_err(eyre::Report::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            encode(&mut writer, obj, format)?;
+            writer.flush()?;
+            Ok(())
+        });
+    if let Err(error) = written {
+        let _ = fs::remove_file(&temporary_path);
+        return Err(error);
+    }
+    fs::rename(&temporary_path, &new_path)?;
+
+    if new_path != path && path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(new_path)
+}
+
+/// Decodes an object from a reader, detecting its format from the first buffered bytes
+fn decode<R: BufRead, T: DeserializeOwned>(mut reader: R) -> Result<T> {
+    let obj: T = match StorageFormat::detect(reader.fill_buf()?) {
+        StorageFormat::Json => serde_json::from_reader(reader)?,
+        StorageFormat::Msgpack => rmp_serde::from_read(reader)?,
+        StorageFormat::MsgpackZstd => rmp_serde::from_read(zstd::Decoder::with_buffer(reader)?)?,
+    };
 
     Ok(obj)
 }
+
+fn encode<W: Write, T: Serialize>(writer: &mut W, obj: &T, format: StorageFormat) -> Result<()> {
+    match format {
+        StorageFormat::Json => serde_json::to_writer_pretty(writer, obj)?,
+        StorageFormat::Msgpack => rmp_serde::encode::write_named(writer, obj)?,
+        StorageFormat::MsgpackZstd => {
+            let mut encoder = zstd::Encoder::new(writer, 0)?;
+            rmp_serde::encode::write_named(&mut encoder, obj)?;
+            encoder.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        bootstrap::{Bootstrap, FrequencyIntervals},
+        objects::{
+            analysis::Analysis,
+            report::{Report, ReportMetadata},
+        },
+        occurance::OccuranceAnalysis,
+        pruning::PrunedMass,
+    };
+
+    fn round_trip<T: Serialize + DeserializeOwned>(obj: &T) {
+        for format in StorageFormat::value_variants() {
+            let mut content = Vec::new();
+            encode(&mut content, obj, *format).unwrap();
+
+            let decoded: T = decode(content.as_slice())
+                .unwrap_or_else(|error| panic!("Cannot decode {format:?}: {error}"));
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(obj).unwrap(),
+                "{format:?}"
+            );
+        }
+    }
+
+    fn test_analysis() -> Analysis {
+        let mut analysis = Analysis::for_test("a", 2);
+        analysis.metadata.specification = Some(
+            serde_json::from_value(json!({
+                "ngram_ns": [1, 2],
+                "skipgram_ns": [1],
+                "sampling": { "method": "fraction", "fraction": 0.5, "seed": 3 },
+                "bootstrap_shards": 2,
+            }))
+            .unwrap(),
+        );
+        analysis.metadata.deduplication = Some(Default::default());
+        let ngrams = analysis.analysis.ngrams.entry(2).or_default();
+        ngrams.increment("ab".into(), 3);
+        analysis.analysis.words.increment("abc".into(), 1);
+        analysis.shards = vec![analysis.analysis.clone(), Default::default()];
+
+        analysis
+    }
+
+    #[test]
+    fn test_storage_formats() {
+        let mut analysis = OccuranceAnalysis::<usize>::default();
+        analysis
+            .ngrams
+            .entry(2)
+            .or_default()
+            .increment("ab".into(), 3);
+        analysis.words.increment("abc".into(), 1);
+        analysis.num_sentences = 1;
+
+        for format in StorageFormat::value_variants() {
+            let mut content = Vec::new();
+            encode(&mut content, &analysis, *format).unwrap();
+
+            assert_eq!(StorageFormat::detect(&content), *format);
+            let decoded: OccuranceAnalysis<usize> = decode(content.as_slice()).unwrap();
+            assert_eq!(decoded, analysis);
+        }
+    }
+
+    #[test]
+    fn test_storage_formats_round_trip() {
+        round_trip(&test_analysis());
+
+        let analysis = test_analysis().analysis;
+        let mut frequencies = analysis.clone() * 1.0;
+        frequencies.normalize();
+        let report = Report {
+            metadata: ReportMetadata {
+                id: "test".to_owned(),
+                name: "Test".to_owned(),
+                languages: vec!["da".to_owned()],
+                version: semver::Version::new(1, 0, 0),
+                extra: json!({ "notes": ["a", 1, 2.5, null], "nested": { "flag": true } }),
+                process_date: Utc::now(),
+            },
+            sources: Vec::new(),
+            count: 0,
+            analysis_counts: analysis,
+            frequency_intervals: Some(FrequencyIntervals {
+                bootstrap: Bootstrap {
+                    replicates: 10,
+                    confidence: 0.9,
+                    seed: 0,
+                },
+                lower: frequencies.clone(),
+                upper: frequencies.clone(),
+            }),
+            analysis_frequencies: frequencies,
+            pruned: [(
+                "ngrams/2".to_owned(),
+                PrunedMass {
+                    entries: 1,
+                    count: 2,
+                    frequency: 0.1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            lengths: Default::default(),
+        };
+        round_trip(&report);
+    }
+
+    #[test]
+    fn test_write_object_replaces_format() {
+        let directory = std::env::temp_dir().join(format!("storage-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let analysis = test_analysis();
+
+        let path = find_object(&directory, "test");
+        let json_path = write_object(&path, &analysis, StorageFormat::Json).unwrap();
+        assert_eq!(json_path, directory.join("test.json"));
+
+        let zstd_path = write_object(&json_path, &analysis, StorageFormat::MsgpackZstd).unwrap();
+        assert_eq!(zstd_path, directory.join("test.msgpack.zst"));
+        assert_eq!(find_object(&directory, "test"), zstd_path);
+        assert!(!json_path.exists());
+
+        let read: Analysis = read_json(&zstd_path).unwrap();
+        assert_eq!(read.source.hash, analysis.source.hash);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashSet, fs::read_to_string, path::Path};
+
+use clap::ValueEnum;
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use unicode_general_category::{get_general_category, GeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::crypt::{digest_sha256, DigestExt};
+
+/// How sentences are split into words
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum WordSegmentation {
+    /// Runs of non-whitespace
+    #[default]
+    Whitespace,
+    /// Unicode word boundaries (UAX #29). Punctuation is split from words into words of its own
+    Unicode,
+    /// Longest words of a dictionary, for languages written without spaces.
+    /// Runs of text matching no word of the dictionary are words of their own
+    Dictionary,
+}
+
+/// Parameters of word tokenisation, stored with the analysis such that word tables are comparable
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WordTokenization {
+    pub segmentation: WordSegmentation,
+
+    /// Punctuation is trimmed from the start and end of words, dropping words of only punctuation
+    #[serde(default)]
+    pub trim_punctuation: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<DictionaryIdentification>,
+}
+
+/// Identifies the dictionary words were matched against
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DictionaryIdentification {
+    pub name: String,
+    pub hash: String,
+    pub words: usize,
+}
+
+/// Word list that the dictionary segmentation matches words of
+#[derive(Debug)]
+pub struct Dictionary {
+    pub name: String,
+    hash: String,
+    words: HashSet<String>,
+    /// Number of graphemes of the longest word
+    max_len: usize,
+}
+
+impl Dictionary {
+    /// Reads a dictionary of one word per line. Anything after a tab, such as a count, is ignored
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = read_to_string(path)
+            .wrap_err_with(|| format!("Error reading dictionary '{}'", path.display()))?;
+
+        Ok(Self::new(
+            &path.file_stem().unwrap_or_default().to_string_lossy(),
+            &digest_sha256(content.as_bytes())?.to_str(),
+            content.lines(),
+        ))
+    }
+
+    pub fn new<'a>(name: &str, hash: &str, words: impl Iterator<Item = &'a str>) -> Self {
+        let words: HashSet<String> = words
+            .filter_map(|line| line.split('\t').next())
+            .map(|word| word.trim().to_owned())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let max_len = words
+            .iter()
+            .map(|word| word.graphemes(true).count())
+            .max()
+            .unwrap_or(1);
+
+        Self {
+            name: name.to_owned(),
+            hash: hash.to_owned(),
+            words,
+            max_len,
+        }
+    }
+
+    pub fn identification(&self) -> DictionaryIdentification {
+        DictionaryIdentification {
+            name: self.name.clone(),
+            hash: self.hash.clone(),
+            words: self.words.len(),
+        }
+    }
+
+    /// Splits text into the longest words of the dictionary, from left to right
+    fn segment<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let boundaries: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect();
+
+        let mut words = Vec::new();
+        let mut unmatched_start: Option<usize> = None;
+        let mut i = 0;
+        while i + 1 < boundaries.len() {
+            let start = boundaries[i];
+            let longest = (i + 1..boundaries.len())
+                .take(self.max_len)
+                .rev()
+                .find(|&end| self.words.contains(&text[start..boundaries[end]]));
+
+            match longest {
+                Some(end) => {
+                    if let Some(unmatched_start) = unmatched_start.take() {
+                        words.push(&text[unmatched_start..start]);
+                    }
+                    words.push(&text[start..boundaries[end]]);
+                    i = end;
+                }
+                None => {
+                    unmatched_start.get_or_insert(start);
+                    i += 1;
+                }
+            }
+        }
+
+        if let Some(unmatched_start) = unmatched_start {
+            words.push(&text[unmatched_start..]);
+        }
+
+        words
+    }
+}
+
+/// Splits sentences into words
+#[derive(Debug, Default)]
+pub struct WordTokenizer {
+    pub segmentation: WordSegmentation,
+    pub trim_punctuation: bool,
+    /// Required by the dictionary segmentation
+    pub dictionary: Option<Dictionary>,
+}
+
+impl WordTokenizer {
+    pub fn tokenization(&self) -> WordTokenization {
+        let dictionary = match self.segmentation {
+            WordSegmentation::Dictionary => self.dictionary.as_ref(),
+            _ => None,
+        };
+
+        WordTokenization {
+            segmentation: self.segmentation,
+            trim_punctuation: self.trim_punctuation,
+            dictionary: dictionary.map(|dictionary| dictionary.identification()),
+        }
+    }
+
+    pub fn words<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
+        let words: Vec<&str> = match (self.segmentation, &self.dictionary) {
+            (WordSegmentation::Unicode, _) => sentence
+                .split_word_bounds()
+                .filter(|word| !word.trim().is_empty())
+                .collect(),
+            (WordSegmentation::Dictionary, Some(dictionary)) => sentence
+                .split_whitespace()
+                .flat_map(|run| dictionary.segment(run))
+                .collect(),
+            _ => sentence.split_whitespace().collect(),
+        };
+
+        if !self.trim_punctuation {
+            return words;
+        }
+
+        words
+            .into_iter()
+            .map(|word| word.trim_matches(is_punctuation))
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            get_general_category(c),
+            GeneralCategory::ConnectorPunctuation
+                | GeneralCategory::DashPunctuation
+                | GeneralCategory::OpenPunctuation
+                | GeneralCategory::ClosePunctuation
+                | GeneralCategory::InitialPunctuation
+                | GeneralCategory::FinalPunctuation
+                | GeneralCategory::OtherPunctuation
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        let sentence = "«hello», world... it's";
+
+        let whitespace = WordTokenizer::default();
+        assert_eq!(
+            whitespace.words(sentence),
+            vec!["«hello»,", "world...", "it's"]
+        );
+
+        let unicode = WordTokenizer {
+            segmentation: WordSegmentation::Unicode,
+            ..Default::default()
+        };
+        assert_eq!(
+            unicode.words(sentence),
+            vec!["«", "hello", "»", ",", "world", ".", ".", ".", "it's"]
+        );
+
+        let trimmed = WordTokenizer {
+            trim_punctuation: true,
+            ..unicode
+        };
+        assert_eq!(trimmed.words(sentence), vec!["hello", "world", "it's"]);
+
+        let dictionary = WordTokenizer {
+            segmentation: WordSegmentation::Dictionary,
+            trim_punctuation: true,
+            dictionary: Some(Dictionary::new(
+                "test",
+                "",
+                ["我", "喜欢", "苹果", "苹"].into_iter(),
+            )),
+        };
+        assert_eq!(
+            dictionary.words("我喜欢吃苹果。"),
+            vec!["我", "喜欢", "吃", "苹果"]
+        );
+    }
+}